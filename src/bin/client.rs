@@ -1,271 +1,316 @@
 // Client.rs - Cobot Simulator
-use assignment2::{ClientMessage, RobotState, ServerMessage, BOUNDARY_HEIGHT, BOUNDARY_WIDTH};
+use assignment2::cli::Args;
+use assignment2::net::{self, ConnectOptions};
+use assignment2::footprint::{self, Placed};
+use assignment2::model::ModelOptions;
+use assignment2::sim::{self, MotionLimits, Robot, Route, SharedRobot, WAYPOINT_TOLERANCE};
+use assignment2::{ModelState, RobotState, BOUNDARY_HEIGHT, BOUNDARY_WIDTH};
 use eframe::egui::{self, Color32, Pos2, CornerRadius, Stroke, Vec2, StrokeKind};
 use rand::Rng;
-use std::io::{BufRead, BufReader, Write};
-use std::net::TcpStream;
-use std::sync::mpsc;
-use std::thread;
-use std::time::{Duration, Instant};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+// Command-line configuration
+struct ClientConfig {
+    net: ConnectOptions,
+    // Simulation and telemetry rate, independent of the window's frame rate
+    rate_hz: f32,
+    limits: MotionLimits,
+    model: ModelOptions,
+    // Followed from the start when given
+    route: Option<Route>,
+    route_path: Option<String>,
+}
+
+impl ClientConfig {
+    fn from_args(args: &Args) -> Result<Self, String> {
+        Ok(Self {
+            net: ConnectOptions::from_args(args)?,
+            rate_hz: sim::rate_from_args(args, sim::DEFAULT_RATE_HZ)?,
+            limits: MotionLimits::from_args(args),
+            model: ModelOptions::from_args(args)?,
+            route: args.value("--waypoints").map(Route::load).transpose()?,
+            route_path: args.value("--waypoints").map(String::from),
+        })
+    }
+}
 
 fn main() -> eframe::Result<()> {
+    let config = ClientConfig::from_args(&Args::from_env()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([400.0, 500.0]),
+        viewport: egui::ViewportBuilder::default().with_inner_size([420.0, 760.0]),
         ..Default::default()
     };
     eframe::run_native(
         "Cobot Client",
         options,
-        Box::new(|_cc| Ok(Box::new(ClientApp::new()))),
+        Box::new(|_cc| Ok(Box::new(ClientApp::new(config)))),
     )
 }
 
 struct ClientApp {
-    // Local Simulation State
-    state: RobotState,
-    target_speed: f32,
-    speed_limit: f32, // Controlled by server
-    
-    // Novel Feature: Wander Mode
-    wander_mode: bool,
-    last_wander_change: Instant,
+    // Simulated on its own thread; the GUI only reads it and applies operator inputs
+    robot: SharedRobot,
 
     // Communication
-    tx_net: Option<mpsc::Sender<ClientMessage>>, // To Network Thread
-    rx_net: mpsc::Receiver<ServerMessage>,       // From Network Thread
-    connection_status: String,
-    
-    last_update: Instant,
+    config: ConnectOptions,
+    rx_log: mpsc::Receiver<String>,
     logs: Vec<String>,
+
+    // Waypoint Navigation: the route being edited, copied to the robot by Follow
+    plan: RoutePlan,
+}
+
+struct RoutePlan {
+    waypoints: Vec<(f32, f32)>,
+    looping: bool,
+    tolerance: f32,
+    // Route file for the Load button
+    path: String,
+}
+
+impl RoutePlan {
+    fn route(&self) -> Route {
+        let mut route = Route::new(self.waypoints.clone(), self.looping);
+        route.tolerance = self.tolerance;
+        route
+    }
+
+    fn load(&mut self, route: &Route) {
+        self.waypoints = route.waypoints.clone();
+        self.looping = route.looping;
+        self.tolerance = route.tolerance;
+    }
 }
 
 impl ClientApp {
-    fn new() -> Self {
-        let (_tx_dummy, rx_net) = mpsc::channel();
-        
+    fn new(config: ClientConfig) -> Self {
+        let (tx_log, rx_log) = mpsc::channel();
+
         // Random start position
         let mut rng = rand::thread_rng();
-        
+        let state = RobotState {
+            id: format!("Cobot-{}", rng.gen_range(100..999)),
+            x: rng.gen_range(50.0..300.0),
+            y: rng.gen_range(50.0..300.0),
+            speed: 0.0,
+            angle: rng.gen_range(0.0..std::f32::consts::TAU),
+            active: true,
+            // Fix: 'gen' is a keyword in Rust 2024, so we use r#gen
+            color: (rng.r#gen(), rng.r#gen(), rng.r#gen()),
+            seq: 0,
+            sent_at_ms: 0,
+            stopping_distance: 0.0,
+            model: ModelState::default(),
+        };
+
+        let mut robot = Robot::new(state, tx_log);
+        robot.limits = config.limits;
+        robot.model = config.model.build(&robot.state);
+        robot.reach = config.net.footprint.reach();
+        let mut plan = RoutePlan {
+            waypoints: Vec::new(),
+            looping: false,
+            tolerance: WAYPOINT_TOLERANCE,
+            path: config.route_path.unwrap_or_else(|| "route.json".to_string()),
+        };
+        if let Some(route) = config.route {
+            plan.load(&route);
+            robot.route = Some(route);
+        }
+        let robot = Arc::new(Mutex::new(robot));
+        sim::spawn(robot.clone(), config.rate_hz);
+
         Self {
-            state: RobotState {
-                id: format!("Cobot-{}", rng.gen_range(100..999)),
-                x: rng.gen_range(50.0..300.0),
-                y: rng.gen_range(50.0..300.0),
-                speed: 0.0,
-                angle: rng.gen_range(0.0..6.28),
-                active: true,
-                // Fix: 'gen' is a keyword in Rust 2024, so we use r#gen
-                color: (rng.r#gen(), rng.r#gen(), rng.r#gen()),
-            },
-            target_speed: 50.0,
-            speed_limit: 200.0,
-            wander_mode: false,
-            last_wander_change: Instant::now(),
-            tx_net: None,
-            rx_net: rx_net, // Temporary, overwritten on connect
-            connection_status: "Disconnected".to_string(),
-            last_update: Instant::now(),
+            robot,
+            config: config.net,
+            rx_log,
             logs: vec!["Welcome. Set ID and Connect.".into()],
+            plan,
         }
     }
 
-    fn connect(&mut self) {
-        let address = "127.0.0.1:5050";
-        self.connection_status = format!("Connecting to {}...", address);
-        
-        // Create channels
-        let (tx_to_net, rx_from_gui) = mpsc::channel::<ClientMessage>();
-        let (tx_to_gui, rx_from_net) = mpsc::channel::<ServerMessage>();
-        
-        self.tx_net = Some(tx_to_net);
-        self.rx_net = rx_from_net;
-
-        let _log_tx = tx_to_gui.clone(); 
-        
-        thread::spawn(move || {
-            match TcpStream::connect(address) {
-                Ok(stream) => {
-                    let stream_clone = stream.try_clone().expect("Clone failed");
-                    
-                    // Reader Thread
-                    let tx_cmd = tx_to_gui.clone();
-                    thread::spawn(move || {
-                        let mut reader = BufReader::new(stream_clone);
-                        let mut line = String::new();
-                        loop {
-                            line.clear();
-                            match reader.read_line(&mut line) {
-                                Ok(0) => break,
-                                Ok(_) => {
-                                    if let Ok(msg) = serde_json::from_str::<ServerMessage>(&line) {
-                                        let _ = tx_cmd.send(msg);
-                                    }
-                                }
-                                Err(_) => break,
-                            }
-                        }
-                    });
+    fn connect(&self, robot: &mut Robot) {
+        robot.avoid = self.config.avoid;
+        robot.attach(net::connect(&self.config, &robot.state.id, robot.log_sender()));
+        robot.log("Network threads started.");
+    }
 
-                    // Writer Loop (on this thread)
-                    let mut writer = stream;
-                    loop {
-                        match rx_from_gui.recv() {
-                            Ok(msg) => {
-                                let json = serde_json::to_string(&msg).unwrap();
-                                if let Err(_) = writer.write_all(format!("{}\n", json).as_bytes()) {
-                                    break;
-                                }
-                                let _ = writer.flush();
-                            }
-                            Err(_) => break,
-                        }
+    // Waypoint Navigation: plan a route on the map or load one, then follow it
+    fn waypoints_ui(&mut self, ui: &mut egui::Ui, robot: &mut Robot) {
+        ui.heading("Waypoints");
+        match &robot.route {
+            Some(route) => ui.label(format!(
+                "Following: waypoint {} of {}{}{}",
+                route.next_index() + 1,
+                route.waypoints.len(),
+                if route.looping { " (looping)" } else { "" },
+                if route.detour.is_empty() { String::new() } else { format!(", {} detour point(s) first", route.detour.len()) }
+            )),
+            None => ui.label(format!("{} waypoint(s) planned. Click the map to add more.", self.plan.waypoints.len())),
+        };
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.plan.looping, "Loop");
+            ui.add(egui::Slider::new(&mut self.plan.tolerance, 2.0..=50.0).text("Tolerance (px)"));
+        });
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!self.plan.waypoints.is_empty(), egui::Button::new("Follow")).clicked() {
+                robot.route = Some(self.plan.route());
+                robot.log(format!("Following {} waypoint(s)", self.plan.waypoints.len()));
+            }
+            if ui.add_enabled(robot.route.is_some(), egui::Button::new("Cancel")).clicked() {
+                robot.route = None;
+                robot.log("Route cancelled");
+            }
+            if ui.button("Undo").clicked() {
+                self.plan.waypoints.pop();
+            }
+            if ui.button("Clear").clicked() {
+                self.plan.waypoints.clear();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.plan.path);
+            if ui.button("Load").clicked() {
+                match Route::load(&self.plan.path) {
+                    Ok(route) => {
+                        robot.log(format!("Loaded {} waypoint(s) from {}", route.waypoints.len(), self.plan.path));
+                        self.plan.load(&route);
                     }
-                }
-                Err(e) => {
-                    // In a real app we'd signal error back to GUI
-                    eprintln!("Failed to connect: {}", e);
+                    Err(e) => robot.log(e),
                 }
             }
         });
-        
-        self.connection_status = "Connected".to_string();
-        self.logs.push("Network threads started.".into());
-    }
 
-    fn update_physics(&mut self) {
-        let now = Instant::now();
-        let dt = now.duration_since(self.last_update).as_secs_f32();
-        self.last_update = now;
-
-        if !self.state.active {
-            return;
+        // Loop and tolerance changes apply to the route being followed too
+        if let Some(route) = &mut robot.route {
+            route.looping = self.plan.looping;
+            route.tolerance = self.plan.tolerance;
         }
+    }
 
-        // Novel Feature: Wander Logic
-        if self.wander_mode && now.duration_since(self.last_wander_change).as_secs_f32() > 2.0 {
-            let mut rng = rand::thread_rng();
-            self.state.angle += rng.gen_range(-1.0..1.0); // Turn slightly
-            self.last_wander_change = now;
+    // Planned waypoints (grey, numbered) and the rest of the route being
+    // followed (yellow), starting with the leg to the next waypoint
+    fn draw_route(&self, painter: &egui::Painter, robot: &Robot, to_mini: impl Fn(f32, f32) -> Pos2) {
+        let grey = Stroke::new(1.0, Color32::GRAY);
+        let planned: Vec<Pos2> = self.plan.waypoints.iter().map(|&(x, y)| to_mini(x, y)).collect();
+        for (i, &p) in planned.iter().enumerate() {
+            painter.circle_stroke(p, 3.0, grey);
+            painter.text(p + Vec2::new(4.0, -4.0), egui::Align2::LEFT_BOTTOM, (i + 1).to_string(), egui::FontId::proportional(9.0), Color32::GRAY);
+        }
+        if self.plan.looping && planned.len() > 2 {
+            painter.add(egui::Shape::closed_line(planned, grey));
+        } else {
+            painter.add(egui::Shape::line(planned, grey));
         }
 
-        // Apply Speed Limit
-        let actual_speed = self.target_speed.min(self.speed_limit);
-        self.state.speed = actual_speed;
-
-        // Move
-        self.state.x += self.state.speed * self.state.angle.cos() * dt;
-        self.state.y += self.state.speed * self.state.angle.sin() * dt;
-
-        // Simple local boundary clamp (client side prediction)
-        // FIXED: Clamp strictly to visible area (0.0 to WIDTH).
-        // The Server triggers alerts at < 10.0 and > WIDTH - 10.0, so hitting 0.0 or WIDTH
-        // will successfully trigger the stop logic without the robot disappearing off-screen.
-        self.state.x = self.state.x.clamp(0.0, BOUNDARY_WIDTH);
-        self.state.y = self.state.y.clamp(0.0, BOUNDARY_HEIGHT);
-    }
-
-    fn send_telemetry(&self) {
-        if let Some(tx) = &self.tx_net {
-            let _ = tx.send(ClientMessage::Telemetry(self.state.clone()));
+        if let Some(route) = &robot.route
+            && let Some((tx, ty)) = route.target()
+        {
+            let yellow = Stroke::new(1.5, Color32::YELLOW);
+            let target = to_mini(tx, ty);
+            painter.line_segment([to_mini(robot.state.x, robot.state.y), target], yellow);
+            painter.circle_filled(target, 3.0, Color32::YELLOW);
+            // Remaining legs; a looping route goes round again
+            let rest = if route.looping { route.waypoints.len() } else { route.waypoints.len() - route.next_index() };
+            let ahead = route.waypoints.iter().cycle().skip(route.next_index()).take(rest + usize::from(route.looping));
+            let points = route.detour.iter().chain(ahead);
+            painter.add(egui::Shape::line(points.map(|&(x, y)| to_mini(x, y)).collect(), yellow));
         }
     }
 }
 
 impl eframe::App for ClientApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // 1. Receive Commands
-        while let Ok(msg) = self.rx_net.try_recv() {
-            match msg {
-                ServerMessage::ForceStop => {
-                    // FIXED: Only process stop if we are currently active (prevents logic loops)
-                    if self.state.active {
-                        self.state.active = false;
-                        self.state.speed = 0.0;
-                        
-                        // BOUNCE LOGIC: 
-                        // 1. Turn 180 degrees
-                        self.state.angle += std::f32::consts::PI; 
-                        
-                        // 2. Hop slightly away from the wall immediately
-                        // This prevents being "stuck" in the wall when you press Go
-                        self.state.x += 15.0 * self.state.angle.cos();
-                        self.state.y += 15.0 * self.state.angle.sin();
-                        
-                        // Ensure the hop doesn't push us out of bounds again
-                        self.state.x = self.state.x.clamp(0.0, BOUNDARY_WIDTH);
-                        self.state.y = self.state.y.clamp(0.0, BOUNDARY_HEIGHT);
-
-                        self.logs.push("CMD: STOPPED (Turned 180° - Press GO to escape)".into());
-                    }
-                }
-                ServerMessage::Resume => {
-                    self.state.active = true;
-                    self.logs.push("SERVER CMD: RESUME".into());
-                }
-                ServerMessage::SetSpeedLimit(limit) => {
-                    self.speed_limit = limit;
-                    self.logs.push(format!("SERVER CMD: Speed Limit {}", limit));
-                }
-                ServerMessage::Warning(txt) => {
-                    self.logs.push(format!("WARNING: {}", txt));
-                }
-            }
+        while let Ok(line) = self.rx_log.try_recv() {
+            self.logs.push(line);
         }
 
-        // 2. Update Physics
-        self.update_physics();
-
-        // 3. Send Telemetry (Throttle to ~10Hz)
-        if self.tx_net.is_some() && self.last_update.elapsed().as_millis() < 20 {
-             self.send_telemetry();
-        }
+        // Held only while laying out the window
+        let shared = self.robot.clone();
+        let mut robot = shared.lock().unwrap();
 
-        // 4. GUI Layout
+        // GUI Layout
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Cobot Client Controller");
             
             ui.horizontal(|ui| {
                 ui.label("Status:");
-                ui.label(if self.tx_net.is_some() { "Online" } else { "Offline" });
-                ui.colored_label(if self.state.active { Color32::GREEN } else { Color32::RED }, 
-                    if self.state.active { "ACTIVE" } else { "STOPPED" });
+                ui.label(if robot.is_connected() { "Online" } else { "Offline" });
+                ui.colored_label(if robot.state.active { Color32::GREEN } else { Color32::RED }, 
+                    if robot.state.active { "ACTIVE" } else { "STOPPED" });
+                if robot.held {
+                    ui.colored_label(Color32::ORANGE, "HELD (traffic)");
+                }
+                if robot.avoiding {
+                    ui.colored_label(Color32::YELLOW, "AVOIDING");
+                }
             });
+            match robot.priority {
+                Some(priority) => ui.label(format!("Priority: {}", priority)),
+                None => ui.label(format!("Priority: {} (declared)", self.config.priority)),
+            };
 
             ui.separator();
-            ui.text_edit_singleline(&mut self.state.id);
-            if self.tx_net.is_none() {
+            ui.text_edit_singleline(&mut robot.state.id);
+            ui.horizontal(|ui| {
+                ui.label("Key:");
+                ui.add(egui::TextEdit::singleline(&mut self.config.key).password(true));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Priority:");
+                let priority = egui::DragValue::new(&mut self.config.priority);
+                ui.add_enabled(!robot.is_connected(), priority)
+                    .on_hover_text("Right of way when robots get too close; the higher level keeps going");
+            });
+            if !robot.is_connected() {
                 if ui.button("Connect").clicked() {
-                    self.connect();
+                    self.connect(&mut robot);
                 }
             } else {
                  if ui.button("Disconnect").clicked() {
                      // In a real app we would drop channels
-                     self.logs.push("Disconnecting...".into());
+                     robot.log("Disconnecting...");
                  }
             }
 
             ui.separator();
             ui.heading("Controls");
-            ui.add(egui::Slider::new(&mut self.target_speed, 0.0..=200.0).text("Target Speed"));
-            ui.add(egui::Slider::new(&mut self.state.angle, 0.0..=6.28).text("Angle (Rad)"));
+            ui.add(egui::Slider::new(&mut robot.target_speed, 0.0..=200.0).text("Target Speed"));
+            ui.add(egui::Slider::new(&mut robot.target_heading, 0.0..=std::f32::consts::TAU).text("Heading (Rad)"));
             
             ui.horizontal(|ui| {
-                if ui.button("Stop").clicked() { self.state.active = false; }
+                if ui.button("Stop").clicked() { robot.state.active = false; }
                 if ui.button("Go").clicked() { 
-                    self.state.active = true; 
+                    robot.state.active = true; 
                     // Reset speed if it was zeroed out
-                    if self.state.speed < 10.0 { self.target_speed = 50.0; } 
+                    if robot.state.speed < 10.0 { robot.target_speed = 50.0; } 
                 }
             });
 
             ui.separator();
-            ui.checkbox(&mut self.wander_mode, "Wander Mode (Novel Feature)");
+            ui.checkbox(&mut robot.wander_mode, "Wander Mode (Novel Feature)");
+            let avoid = egui::Checkbox::new(&mut self.config.avoid, "Local Avoidance");
+            ui.add_enabled(!robot.is_connected(), avoid)
+                .on_hover_text("Ask the server for nearby robots when connecting, and steer round them");
+
+            ui.separator();
+            self.waypoints_ui(ui, &mut robot);
 
             ui.separator();
-            ui.label(format!("Pos: ({:.1}, {:.1})", self.state.x, self.state.y));
+            ui.label(format!("Pos: ({:.1}, {:.1})", robot.state.x, robot.state.y));
+            ui.label(format!(
+                "Speed: {:.0}  Heading: {:.2} rad  Stopping distance: {:.1}",
+                robot.state.speed, robot.state.angle, robot.state.stopping_distance
+            ));
+            ui.label(format!("Drive: {}", robot.state.model.describe()));
             
             // Mini Preview
-            let (response, painter) = ui.allocate_painter(Vec2::new(300.0, 200.0), egui::Sense::hover());
+            let (response, painter) = ui.allocate_painter(Vec2::new(300.0, 200.0), egui::Sense::click());
             let rect = response.rect;
             // Fix: Updated to CornerRadius and added StrokeKind
             painter.rect_stroke(rect, CornerRadius::default(), Stroke::new(1.0, Color32::GRAY), StrokeKind::Middle);
@@ -276,12 +321,46 @@ impl eframe::App for ClientApp {
                 let my = rect.min.y + (y / BOUNDARY_HEIGHT) * rect.height();
                 Pos2::new(mx, my)
             };
+
+            // Clicking the map adds a waypoint to the plan
+            if response.clicked()
+                && let Some(click) = response.interact_pointer_pos()
+            {
+                let x = (click.x - rect.min.x) / rect.width() * BOUNDARY_WIDTH;
+                let y = (click.y - rect.min.y) / rect.height() * BOUNDARY_HEIGHT;
+                self.plan.waypoints.push((x, y));
+            }
+            self.draw_route(&painter, &robot, to_mini);
+
+            // Neighbours reported by the server
+            let scale = rect.width() / BOUNDARY_WIDTH;
+            for n in &robot.neighbours {
+                painter.circle_stroke(to_mini(n.x, n.y), n.reach * scale, Stroke::new(1.0, Color32::GRAY));
+            }
             
-            painter.circle_filled(
-                to_mini(self.state.x, self.state.y), 
-                5.0, 
-                Color32::from_rgb(self.state.color.0, self.state.color.1, self.state.color.2)
-            );
+            let pos = to_mini(robot.state.x, robot.state.y);
+            let color = Color32::from_rgb(robot.state.color.0, robot.state.color.1, robot.state.color.2);
+            let footprint = &self.config.footprint;
+            match footprint.place(robot.state.x, robot.state.y, robot.state.body_heading()) {
+                Placed::Circle { radius, .. } => {
+                    painter.circle_filled(pos, radius * rect.width() / BOUNDARY_WIDTH, color);
+                }
+                Placed::Polygon(points) => {
+                    // Triangulated, since outlines may be concave
+                    let mut mesh = egui::Mesh::default();
+                    for &(x, y) in &points {
+                        mesh.colored_vertex(to_mini(x, y), color);
+                    }
+                    for [a, b, c] in footprint::triangulate(&points) {
+                        mesh.add_triangle(a as u32, b as u32, c as u32);
+                    }
+                    painter.add(egui::Shape::mesh(mesh));
+                }
+            }
+            // Tick showing which way the body faces
+            let reach = footprint.reach() * rect.width() / BOUNDARY_WIDTH;
+            let facing = Vec2::angled(robot.state.body_heading()) * (reach + 4.0);
+            painter.line_segment([pos, pos + facing], Stroke::new(2.0, color));
 
             ui.separator();
             egui::ScrollArea::vertical().max_height(100.0).show(ui, |ui| {
@@ -0,0 +1,267 @@
+// Fleet simulator: many robots in one process, each with its own connection,
+// for load-testing the server. Robots share one simulation thread.
+//
+// Quick start without a file: `--count 20 --behaviour wander`.
+// A fleet file is a JSON object; omitted fields fall back to the defaults:
+//   {
+//     "rate_hz": 20,
+//     "auto_resume": 5.0,
+//     "robots": [
+//       { "id": "Line", "x": 100, "y": 200, "heading": 0, "speed": 60, "behaviour": "straight" },
+//       { "id": "Post", "x": 300, "y": 300, "color": [200, 200, 200], "behaviour": "static" },
+//       { "id": "W", "count": 10, "behaviour": "wander" },
+//       { "id": "Cart", "count": 4, "model": "holonomic", "behaviour": "wander" },
+//       { "id": "Patrol", "speed": 80, "behaviour": { "waypoint_loop": [[100, 100], [500, 100], [500, 300]] } }
+//     ]
+//   }
+// An entry with `count` spawns that many robots named `<id>-1`, `<id>-2`, ...
+// Missing positions, headings and colours are random. `model` (unicycle,
+// differential_drive or holonomic) overrides --model, and `footprint`
+// (e.g. { "rectangle": { "length": 60, "width": 30 } }) overrides --footprint,
+// and `priority` (0-255, e.g. 10 for an emergency cart) overrides --priority.
+use assignment2::cli::Args;
+use assignment2::net::{self, ConnectOptions};
+use assignment2::footprint::Footprint;
+use assignment2::model::{ModelKind, ModelOptions};
+use assignment2::sim::{self, MotionLimits, Robot, Route, SharedRobot};
+use assignment2::{ModelState, RobotState, BOUNDARY_HEIGHT, BOUNDARY_WIDTH};
+use rand::Rng;
+use serde::Deserialize;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+// Random start positions keep clear of the boundary checks
+const SPAWN_MARGIN: f32 = 50.0;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+enum Behaviour {
+    // Stays where it is
+    Static,
+    // Keeps its heading
+    #[default]
+    Straight,
+    // Wander Mode, as in the GUI client
+    Wander,
+    // Drives round the waypoints forever
+    WaypointLoop(Vec<(f32, f32)>),
+}
+
+#[derive(Deserialize)]
+struct RobotSpec {
+    id: String,
+    #[serde(default)]
+    count: Option<usize>,
+    x: Option<f32>,
+    y: Option<f32>,
+    // Degrees, clockwise from +x (screen coordinates)
+    heading: Option<f32>,
+    speed: Option<f32>,
+    color: Option<(u8, u8, u8)>,
+    #[serde(default)]
+    behaviour: Behaviour,
+    // Overrides --model for this robot
+    model: Option<ModelKind>,
+    // Overrides --footprint for this robot
+    footprint: Option<Footprint>,
+    // Overrides --priority for this robot
+    priority: Option<u8>,
+    // Overrides --key for this robot
+    key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FleetFile {
+    rate_hz: Option<f32>,
+    auto_resume: Option<f32>,
+    robots: Vec<RobotSpec>,
+}
+
+// Command-line configuration
+struct FleetConfig {
+    net: ConnectOptions,
+    rate_hz: f32,
+    // Seconds a robot that stopped by itself waits before pressing Go again;
+    // None never resumes. Server stops always wait for the server's Resume.
+    auto_resume: Option<Duration>,
+    // Same motion limits for every robot
+    limits: MotionLimits,
+    model: ModelOptions,
+    robots: Vec<RobotSpec>,
+}
+
+impl FleetConfig {
+    fn from_args(args: &Args) -> Result<Self, String> {
+        let file = match args.value("--config") {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+                serde_json::from_str(&text).map_err(|e| format!("Invalid fleet file {}: {}", path, e))?
+            }
+            None => {
+                let behaviour = match args.value("--behaviour").unwrap_or("wander") {
+                    "static" => Behaviour::Static,
+                    "straight" => Behaviour::Straight,
+                    "wander" => Behaviour::Wander,
+                    other => return Err(format!("Unknown behaviour '{}' (expected static, straight or wander)", other)),
+                };
+                FleetFile {
+                    rate_hz: None,
+                    auto_resume: None,
+                    robots: vec![RobotSpec {
+                        id: args.value("--prefix").unwrap_or("Fleet").to_string(),
+                        count: Some(args.parse_or("--count", 10)),
+                        x: None,
+                        y: None,
+                        heading: None,
+                        speed: args
+                            .value("--speed")
+                            .map(|raw| {
+                                raw.parse().ok().filter(|s: &f32| s.is_finite()).ok_or_else(|| format!("Invalid --speed: {}", raw))
+                            })
+                            .transpose()?,
+                        color: None,
+                        behaviour,
+                        model: None,
+                        footprint: None,
+                        priority: None,
+                        key: None,
+                    }],
+                }
+            }
+        };
+
+        for spec in &file.robots {
+            if let Some(footprint) = &spec.footprint {
+                footprint.validate().map_err(|e| format!("Robot '{}': {}", spec.id, e))?;
+            }
+        }
+
+        // Command-line values win over the file
+        let auto_resume = args.parse_or("--auto-resume", file.auto_resume.unwrap_or(0.0));
+        Ok(Self {
+            net: ConnectOptions::from_args(args)?,
+            rate_hz: sim::rate_from_args(args, file.rate_hz.unwrap_or(sim::DEFAULT_RATE_HZ))?,
+            auto_resume: match auto_resume {
+                // 0 (or less) turns automatic resumes off
+                t if t <= 0.0 => None,
+                t => Some(Duration::try_from_secs_f32(t).map_err(|_| format!("Invalid --auto-resume: {}", t))?),
+            },
+            limits: MotionLimits::from_args(args),
+            model: ModelOptions::from_args(args)?,
+            robots: file.robots,
+        })
+    }
+}
+
+struct FleetRobot {
+    id: String,
+    robot: SharedRobot,
+    rx_log: mpsc::Receiver<String>,
+    stopped_since: Option<Instant>,
+}
+
+fn build(spec: &RobotSpec, id: String, config: &FleetConfig) -> FleetRobot {
+    let mut rng = rand::thread_rng();
+    let state = RobotState {
+        id: id.clone(),
+        x: spec.x.unwrap_or_else(|| rng.gen_range(SPAWN_MARGIN..BOUNDARY_WIDTH - SPAWN_MARGIN)),
+        y: spec.y.unwrap_or_else(|| rng.gen_range(SPAWN_MARGIN..BOUNDARY_HEIGHT - SPAWN_MARGIN)),
+        speed: 0.0,
+        angle: spec.heading.map_or_else(|| rng.gen_range(0.0..std::f32::consts::TAU), f32::to_radians),
+        active: true,
+        color: spec.color.unwrap_or_else(|| (rng.r#gen(), rng.r#gen(), rng.r#gen())),
+        seq: 0,
+        sent_at_ms: 0,
+        stopping_distance: 0.0,
+        model: ModelState::default(),
+    };
+
+    let (tx_log, rx_log) = mpsc::channel();
+    let mut robot = Robot::new(state, tx_log);
+    robot.limits = config.limits;
+    let mut model = config.model;
+    if let Some(kind) = spec.model {
+        model.kind = kind;
+    }
+    robot.model = model.build(&robot.state);
+    if let Some(speed) = spec.speed {
+        robot.target_speed = speed;
+    }
+    match &spec.behaviour {
+        Behaviour::Static => robot.target_speed = 0.0,
+        Behaviour::Straight => {}
+        Behaviour::Wander => robot.wander_mode = true,
+        Behaviour::WaypointLoop(points) => robot.route = Some(Route::new(points.clone(), true)),
+    }
+
+    let mut options = config.net.clone();
+    if let Some(key) = &spec.key {
+        options.key = key.clone();
+    }
+    if let Some(footprint) = &spec.footprint {
+        options.footprint = footprint.clone();
+    }
+    if let Some(priority) = spec.priority {
+        options.priority = priority;
+    }
+    robot.avoid = options.avoid;
+    robot.reach = options.footprint.reach();
+    robot.attach(net::connect(&options, &id, robot.log_sender()));
+    FleetRobot { id, robot: Arc::new(Mutex::new(robot)), rx_log, stopped_since: None }
+}
+
+fn main() {
+    let config = FleetConfig::from_args(&Args::from_env()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let mut fleet = Vec::new();
+    for spec in &config.robots {
+        match spec.count {
+            Some(n) => fleet.extend((1..=n).map(|i| build(spec, format!("{}-{}", spec.id, i), &config))),
+            None => fleet.push(build(spec, spec.id.clone(), &config)),
+        }
+    }
+    println!("Started {} robots against {}", fleet.len(), config.net.server_addr);
+    sim::spawn_many(fleet.iter().map(|f| f.robot.clone()).collect(), config.rate_hz);
+
+    let mut next_status = Instant::now() + STATUS_INTERVAL;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        for f in &mut fleet {
+            while let Ok(line) = f.rx_log.try_recv() {
+                println!("[{}] {}", f.id, line);
+            }
+
+            // Press Go again some time after a robot stopped by itself (e.g. at
+            // the end of a route), so the fleet keeps moving. A stop from the
+            // server, such as a safety stop or EMERGENCY STOP ALL, is never
+            // overridden.
+            let mut robot = f.robot.lock().unwrap();
+            if robot.state.active || !robot.is_connected() || robot.server_stop {
+                f.stopped_since = None;
+            } else if let Some(delay) = config.auto_resume {
+                let since = *f.stopped_since.get_or_insert_with(Instant::now);
+                if since.elapsed() >= delay {
+                    robot.state.active = true;
+                    f.stopped_since = None;
+                }
+            }
+        }
+
+        if Instant::now() >= next_status {
+            next_status += STATUS_INTERVAL;
+            let (mut connected, mut active) = (0, 0);
+            for f in &fleet {
+                let robot = f.robot.lock().unwrap();
+                connected += usize::from(robot.is_connected());
+                active += usize::from(robot.is_connected() && robot.state.active);
+            }
+            println!("Status: {}/{} connected, {} active", connected, fleet.len(), active);
+        }
+    }
+}
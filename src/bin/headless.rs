@@ -0,0 +1,226 @@
+// Headless robot client for scripted runs and machines without a display.
+// Runs the same simulation as the GUI client and logs to stdout.
+//
+// A mission file is a JSON list of timed steps. `after` is the delay in
+// seconds from the previous step; every other field is optional:
+//   [
+//     { "after": 0, "speed": 60, "heading": 0 },
+//     { "after": 3, "heading": 90 },
+//     { "after": 2, "active": false },
+//     { "after": 1, "active": true, "wander": true }
+//   ]
+use assignment2::cli::Args;
+use assignment2::net::{self, ConnectOptions};
+use assignment2::model::ModelOptions;
+use assignment2::sim::{self, MotionLimits, Robot, Route};
+use assignment2::{ModelState, RobotState};
+use rand::Rng;
+use serde::Deserialize;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize)]
+struct MissionStep {
+    #[serde(default)]
+    after: f32,
+    speed: Option<f32>,
+    // Degrees, clockwise from +x (screen coordinates)
+    heading: Option<f32>,
+    active: Option<bool>,
+    wander: Option<bool>,
+}
+
+impl MissionStep {
+    // e.g. "speed=60 heading=90"
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(speed) = self.speed {
+            parts.push(format!("speed={}", speed));
+        }
+        if let Some(heading) = self.heading {
+            parts.push(format!("heading={}", heading));
+        }
+        if let Some(active) = self.active {
+            parts.push(if active { "go".to_string() } else { "stop".to_string() });
+        }
+        if let Some(wander) = self.wander {
+            parts.push(format!("wander={}", wander));
+        }
+        parts.join(" ")
+    }
+
+    fn apply(&self, robot: &mut Robot) {
+        if let Some(speed) = self.speed {
+            robot.target_speed = speed;
+        }
+        if let Some(heading) = self.heading {
+            robot.target_heading = heading.to_radians();
+        }
+        if let Some(active) = self.active {
+            robot.state.active = active;
+        }
+        if let Some(wander) = self.wander {
+            robot.wander_mode = wander;
+        }
+    }
+}
+
+// Steps with their absolute times from the start
+fn load_mission(path: &str) -> Result<Vec<(Duration, MissionStep)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    let steps: Vec<MissionStep> =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid mission file {}: {}", path, e))?;
+    let mut at = Duration::ZERO;
+    steps
+        .into_iter()
+        .enumerate()
+        .map(|(i, step)| {
+            at = Duration::try_from_secs_f32(step.after.max(0.0))
+                .ok()
+                .and_then(|after| at.checked_add(after))
+                .ok_or_else(|| format!("Invalid mission step {} in {}: after {}", i + 1, path, step.after))?;
+            Ok((at, step))
+        })
+        .collect()
+}
+
+// Command-line configuration
+struct HeadlessConfig {
+    net: ConnectOptions,
+    id: String,
+    x: f32,
+    y: f32,
+    speed: f32,
+    heading: f32,
+    wander: bool,
+    rate_hz: f32,
+    limits: MotionLimits,
+    model: ModelOptions,
+    // Followed from the start, steering instead of --heading
+    route: Option<Route>,
+    mission: Vec<(Duration, MissionStep)>,
+    // Exit after this long; runs until killed otherwise
+    duration: Option<Duration>,
+}
+
+impl HeadlessConfig {
+    fn from_args(args: &Args) -> Result<Self, String> {
+        let mut rng = rand::thread_rng();
+        Ok(Self {
+            net: ConnectOptions::from_args(args)?,
+            id: args.value("--id").map_or_else(|| format!("Headless-{}", rng.gen_range(100..999)), String::from),
+            x: args.parse_or("--x", 100.0),
+            y: args.parse_or("--y", 100.0),
+            speed: args.parse_or("--speed", 50.0),
+            heading: args.parse_or("--heading", 0.0),
+            wander: args.flag("--wander"),
+            rate_hz: sim::rate_from_args(args, sim::DEFAULT_RATE_HZ)?,
+            limits: MotionLimits::from_args(args),
+            model: ModelOptions::from_args(args)?,
+            route: args.value("--waypoints").map(Route::load).transpose()?,
+            mission: args.value("--mission").map_or(Ok(Vec::new()), load_mission)?,
+            duration: args
+                .value("--duration")
+                .map(|raw| {
+                    raw.parse()
+                        .ok()
+                        .and_then(|d| Duration::try_from_secs_f32(d).ok())
+                        .ok_or_else(|| format!("Invalid --duration: {}", raw))
+                })
+                .transpose()?,
+        })
+    }
+}
+
+fn main() {
+    let config = HeadlessConfig::from_args(&Args::from_env()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let (tx_log, rx_log) = mpsc::channel();
+    let mut rng = rand::thread_rng();
+    let state = RobotState {
+        id: config.id.clone(),
+        x: config.x,
+        y: config.y,
+        speed: 0.0,
+        angle: config.heading.to_radians(),
+        active: true,
+        color: (rng.r#gen(), rng.r#gen(), rng.r#gen()),
+        seq: 0,
+        sent_at_ms: 0,
+        stopping_distance: 0.0,
+        model: ModelState::default(),
+    };
+    let mut robot = Robot::new(state, tx_log);
+    robot.target_speed = config.speed;
+    robot.limits = config.limits;
+    robot.model = config.model.build(&robot.state);
+    robot.wander_mode = config.wander;
+    robot.route = config.route;
+    robot.avoid = config.net.avoid;
+    robot.reach = config.net.footprint.reach();
+    robot.attach(net::connect(&config.net, &config.id, robot.log_sender()));
+    let robot = Arc::new(Mutex::new(robot));
+
+    println!("{} connecting to {}", config.id, config.net.server_addr);
+    let start = Instant::now();
+    sim::spawn(robot.clone(), config.rate_hz);
+
+    let mut mission = config.mission.into_iter().peekable();
+    let mut next_status = STATUS_INTERVAL;
+
+    loop {
+        let elapsed = start.elapsed();
+        if config.duration.is_some_and(|d| elapsed >= d) {
+            println!("[{:7.2}s] Duration reached, exiting", elapsed.as_secs_f32());
+            return;
+        }
+        while let Some((_, step)) = mission.next_if(|(at, _)| *at <= elapsed) {
+            println!("[{:7.2}s] Mission step: {}", elapsed.as_secs_f32(), step.describe());
+            step.apply(&mut robot.lock().unwrap());
+        }
+        if elapsed >= next_status {
+            next_status += STATUS_INTERVAL;
+            let robot = robot.lock().unwrap();
+            if !robot.is_connected() {
+                println!("[{:7.2}s] {}, exiting", elapsed.as_secs_f32(), robot.connection_status);
+                std::process::exit(1);
+            }
+            let s = &robot.state;
+            println!(
+                "[{:7.2}s] pos=({:.1}, {:.1}) speed={:.1} heading={:.0} {} {}",
+                elapsed.as_secs_f32(),
+                s.x,
+                s.y,
+                s.speed,
+                s.angle.to_degrees().rem_euclid(360.0),
+                match (s.active, robot.held, robot.avoiding) {
+                    (false, _, _) => "STOPPED",
+                    (true, true, _) => "HELD",
+                    (true, false, true) => "AVOIDING",
+                    (true, false, false) => "ACTIVE",
+                },
+                s.model.describe()
+            );
+        }
+
+        // Sleep until the next thing to do, printing robot logs as they arrive
+        let mut wake = next_status;
+        if let Some((at, _)) = mission.peek() {
+            wake = wake.min(*at);
+        }
+        if let Some(d) = config.duration {
+            wake = wake.min(d);
+        }
+        match rx_log.recv_timeout(wake.saturating_sub(start.elapsed())) {
+            Ok(line) => println!("[{:7.2}s] {}", start.elapsed().as_secs_f32(), line),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
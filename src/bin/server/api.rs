@@ -0,0 +1,441 @@
+// Local HTTP/JSON control API, so tools like the MES can drive the fleet without the GUI.
+//
+//   GET  /robots                     -> latest RobotState of every robot
+//   GET  /snapshot                   -> one FleetSnapshot (robots, proximity pairs, safety events)
+//   POST /robots/{id}/stop           -> ForceStop one robot
+//   POST /robots/{id}/resume         -> Resume one robot
+//   POST /robots/{id}/speed-limit    -> body {"limit": 80.0}
+//   POST /robots/{id}/priority       -> body {"priority": 10}; right of way when robots get too close
+//   POST /robots/{id}/goto           -> body {"x": 300, "y": 200}; replies with the mission ID
+//   POST /robots/{id}/path           -> body {"waypoints": [[100, 100], [300, 100]], "looping": false}
+//   POST /fleet/stop | /fleet/resume | /fleet/speed-limit
+//   GET  /map                        -> obstacles and keep-out zones (see map.rs)
+//   POST /robots/{id}/navigate       -> body {"x": 300, "y": 200}; planned around the map, replies with the mission ID
+//   GET  /traffic                    -> cells reserved by the traffic manager and the robots it holds
+//   GET  /jobs                       -> transport jobs, open ones first
+//   POST /jobs                       -> body {"pickup": [100, 100], "dropoff": [300, 200]}; replies with the job ID
+//   POST /jobs/{id}/cancel           -> cancel a job, stopping the robot carrying it out
+//   GET  /ws                         -> WebSocket fleet snapshot stream (see stream.rs)
+//   GET  /                           -> read-only browser dashboard (dashboard.html)
+//
+// POST requests need `Authorization: Bearer <token>` naming an operator (see operators.rs);
+// the operator's role decides which commands are allowed.
+use crate::control::{Command, CommandError, FleetControl, Target};
+use crate::jobs::JobBoard;
+use crate::operators::{Operator, Operators};
+use crate::safety::SharedSafety;
+use crate::traffic::SharedTraffic;
+use crate::stream;
+use crate::link::LinkStats;
+use crate::mission::Mission;
+use assignment2::footprint::Footprint;
+use assignment2::RobotState;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+const MAX_BODY: usize = 64 * 1024;
+// Longest request or header line, and most header lines, accepted
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+// Connections handled at once, each on its own thread. WebSocket streams stay
+// open, so this bounds the threads idle dashboards can tie up.
+const MAX_CONNECTIONS: usize = 64;
+// Self-contained page (no external assets) so it works on an offline plant network
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+#[derive(Serialize)]
+struct RobotSummary {
+    state: RobotState,
+    footprint: Footprint,
+    priority: u8,
+    last_seen_ms: u128,
+    // Time since the position was measured on the robot
+    data_age_ms: u128,
+    link: LinkStats,
+    // Age of the oldest ForceStop the robot has not acknowledged past the deadline
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unacked_stop_ms: Option<u128>,
+    // Latest mission and its progress
+    mission: Option<Mission>,
+}
+
+#[derive(Deserialize)]
+struct SpeedLimitBody {
+    limit: f32,
+}
+
+#[derive(Deserialize)]
+struct PriorityBody {
+    priority: u8,
+}
+
+#[derive(Deserialize)]
+struct GoToBody {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+struct PathBody {
+    waypoints: Vec<(f32, f32)>,
+    #[serde(default)]
+    looping: bool,
+}
+
+#[derive(Deserialize)]
+struct JobBody {
+    pickup: (f32, f32),
+    dropoff: (f32, f32),
+}
+
+// Everything a request handler may need
+#[derive(Clone)]
+pub struct ApiContext {
+    pub operators: Arc<Operators>,
+    pub control: FleetControl,
+    pub jobs: JobBoard,
+    pub safety: SharedSafety,
+    pub traffic: SharedTraffic,
+    pub stream_hz: f32,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Option<String>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn bearer_token(&self) -> Option<&str> {
+        self.header("Authorization")?.strip_prefix("Bearer ").map(str::trim)
+    }
+
+    fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .as_deref()?
+            .split('&')
+            .filter_map(|kv| kv.split_once('='))
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v)
+    }
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Self { status, content_type: "application/json", body: serde_json::to_string(value).unwrap() }
+    }
+
+    fn html(body: &str) -> Self {
+        Self { status: 200, content_type: "text/html; charset=utf-8", body: body.to_string() }
+    }
+
+    fn error(status: u16, msg: impl Into<String>) -> Self {
+        Self::json(status, &serde_json::json!({ "error": msg.into() }))
+    }
+}
+
+// One of the MAX_CONNECTIONS, given back when the handler ends (or panics)
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn spawn(addr: String, ctx: ApiContext, tx_log: mpsc::Sender<String>) {
+    thread::spawn(move || {
+        let listener = match TcpListener::bind(&addr) {
+            Ok(l) => l,
+            Err(e) => {
+                let _ = tx_log.send(format!("Control API failed to bind {}: {}", addr, e));
+                return;
+            }
+        };
+        let _ = tx_log.send(format!("Control API listening on http://{}", addr));
+        if !ctx.operators.has_api_tokens() {
+            let _ = tx_log.send("Control API is read-only (no API tokens configured)".into());
+        }
+
+        let active = Arc::new(AtomicUsize::new(0));
+        for mut stream in listener.incoming().flatten() {
+            if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                active.fetch_sub(1, Ordering::SeqCst);
+                // Answered without reading the request; a short timeout keeps a
+                // client that does not read from holding up the listener
+                let _ = stream.set_write_timeout(Some(Duration::from_millis(100)));
+                let _ = write_response(&mut stream, &Response::error(503, "Too many connections"));
+                continue;
+            }
+            let ctx = ctx.clone();
+            let slot = Slot(active.clone());
+            thread::spawn(move || {
+                let _slot = slot;
+                let _ = handle_connection(stream, &ctx);
+            });
+        }
+    });
+}
+
+fn handle_connection(mut stream: TcpStream, ctx: &ApiContext) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let req = match read_request(&stream) {
+        Ok(req) => req,
+        Err(e) => return write_response(&mut stream, &Response::error(400, e)),
+    };
+
+    if req.method == "GET" && req.path == "/ws" {
+        let Some(key) = req.header("Sec-WebSocket-Key") else {
+            return write_response(&mut stream, &Response::error(400, "Expected a WebSocket upgrade"));
+        };
+        // NaN and infinities would survive the clamp in `serve`
+        let hz = req.query_param("hz").and_then(|v| v.parse().ok()).filter(|hz: &f32| hz.is_finite());
+        let hz = hz.unwrap_or(ctx.stream_hz);
+        let robots = ctx.control.robots().clone();
+        return stream::serve(stream, key, hz, robots, ctx.safety.clone());
+    }
+
+    let response = route(&req, ctx);
+    write_response(&mut stream, &response)
+}
+
+// Reads one line into `line`, refusing lines longer than MAX_LINE
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<(), String> {
+    line.clear();
+    let read = reader.take(MAX_LINE as u64 + 1).read_line(line).map_err(|e| e.to_string())?;
+    if read > MAX_LINE {
+        return Err("Request line or header too long".into());
+    }
+    Ok(())
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    read_line(&mut reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or("Missing method")?.to_string();
+    let target = parts.next().ok_or("Missing path")?;
+    let (path, query) = match target.split_once('?') {
+        Some((p, q)) => (p.to_string(), Some(q.to_string())),
+        None => (target.to_string(), None),
+    };
+
+    let mut headers = Vec::new();
+    for count in 0.. {
+        read_line(&mut reader, &mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if count == MAX_HEADERS {
+            return Err("Too many headers".into());
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let mut req = Request { method, path, query, headers, body: Vec::new() };
+    let content_length: usize = match req.header("Content-Length") {
+        Some(v) => v.parse().map_err(|_| "Bad Content-Length")?,
+        None => 0,
+    };
+    if content_length > MAX_BODY {
+        return Err("Body too large".into());
+    }
+    req.body = vec![0; content_length];
+    reader.read_exact(&mut req.body).map_err(|e| e.to_string())?;
+
+    Ok(req)
+}
+
+fn route(req: &Request, ctx: &ApiContext) -> Response {
+    let segments: Vec<&str> = req.path.trim_matches('/').split('/').collect();
+
+    match (req.method.as_str(), segments.as_slice()) {
+        ("GET", [""] | ["dashboard"]) => Response::html(DASHBOARD_HTML),
+        ("GET", ["robots"]) => {
+            let guard = ctx.control.robots().lock().unwrap();
+            let mut robots: Vec<RobotSummary> = guard
+                .values()
+                .map(|r| RobotSummary {
+                    state: r.state.clone(),
+                    footprint: r.footprint.clone(),
+                    priority: r.priority,
+                    last_seen_ms: r.last_seen.elapsed().as_millis(),
+                    data_age_ms: r.data_age().as_millis(),
+                    link: r.link.clone(),
+                    unacked_stop_ms: r.outbox.lock().unwrap().overdue_stop().map(|age| age.as_millis()),
+                    mission: r.mission.clone(),
+                })
+                .collect();
+            robots.sort_by(|a, b| a.state.id.cmp(&b.state.id));
+            Response::json(200, &robots)
+        }
+        ("GET", ["snapshot"]) => Response::json(200, &stream::snapshot(ctx.control.robots(), &ctx.safety)),
+        ("GET", ["traffic"]) => {
+            let traffic = ctx.traffic.lock().unwrap();
+            let mut reserved: Vec<_> = traffic.reserved.iter().map(|(cell, id)| (id, cell)).collect();
+            reserved.sort();
+            let reserved: Vec<_> = reserved.into_iter().map(|(id, cell)| serde_json::json!({ "robot": id, "cell": cell })).collect();
+            Response::json(200, &serde_json::json!({ "cell_size": crate::traffic::CELL, "reserved": reserved, "held": traffic.held }))
+        }
+        ("GET", ["map"]) => Response::json(200, ctx.control.map()),
+        ("GET", ["jobs"]) => Response::json(200, &ctx.jobs.list()),
+        ("POST", ["jobs"]) => {
+            let operator = match operator(req, ctx) {
+                Ok(op) => op,
+                Err(response) => return response,
+            };
+            match serde_json::from_slice::<JobBody>(&req.body) {
+                Ok(body) => match ctx.jobs.add(body.pickup, body.dropoff, &operator) {
+                    Ok(id) => Response::json(200, &serde_json::json!({ "ok": true, "job": id, "operator": operator.name })),
+                    Err(e) => command_error(e),
+                },
+                Err(e) => Response::error(400, format!("Expected {{\"pickup\": [x, y], \"dropoff\": [x, y]}}: {}", e)),
+            }
+        }
+        ("POST", ["jobs", id, "cancel"]) => {
+            let operator = match operator(req, ctx) {
+                Ok(op) => op,
+                Err(response) => return response,
+            };
+            let Ok(id) = id.parse() else {
+                return Response::error(404, format!("Unknown job: {}", id));
+            };
+            match ctx.jobs.cancel(id, &operator) {
+                Ok(()) => Response::json(200, &serde_json::json!({ "ok": true, "operator": operator.name })),
+                Err(e) => command_error(e),
+            }
+        }
+        ("POST", ["fleet", action]) => command(req, ctx, Target::All, action),
+        ("POST", ["robots", id, action]) => command(req, ctx, Target::Robot(percent_decode(id)), action),
+        ("GET" | "POST", _) => Response::error(404, "Not found"),
+        _ => Response::error(405, "Method not allowed"),
+    }
+}
+
+// Operator named by the request's bearer token
+fn operator(req: &Request, ctx: &ApiContext) -> Result<Operator, Response> {
+    if !ctx.operators.has_api_tokens() {
+        return Err(Response::error(403, "Commands disabled: no API tokens configured"));
+    }
+    req.bearer_token()
+        .and_then(|t| ctx.operators.by_token(t))
+        .ok_or_else(|| Response::error(401, "Missing or invalid bearer token"))
+}
+
+fn command_error(e: CommandError) -> Response {
+    match e {
+        CommandError::UnknownRobot(_) | CommandError::UnknownJob(_) => Response::error(404, e.to_string()),
+        CommandError::Forbidden(_) => Response::error(403, e.to_string()),
+        CommandError::Invalid(_) => Response::error(400, e.to_string()),
+    }
+}
+
+fn command(req: &Request, ctx: &ApiContext, target: Target, action: &str) -> Response {
+    let operator = match operator(req, ctx) {
+        Ok(op) => op,
+        Err(response) => return response,
+    };
+
+    let cmd = match action {
+        "stop" => Command::Stop,
+        "resume" => Command::Resume,
+        "speed-limit" => match serde_json::from_slice::<SpeedLimitBody>(&req.body) {
+            Ok(body) => Command::SetSpeedLimit(body.limit),
+            Err(e) => return Response::error(400, format!("Expected {{\"limit\": <number>}}: {}", e)),
+        },
+        "priority" => match serde_json::from_slice::<PriorityBody>(&req.body) {
+            Ok(body) => Command::SetPriority(body.priority),
+            Err(e) => return Response::error(400, format!("Expected {{\"priority\": <0-255>}}: {}", e)),
+        },
+        "goto" => match serde_json::from_slice::<GoToBody>(&req.body) {
+            Ok(body) => Command::GoTo { x: body.x, y: body.y },
+            Err(e) => return Response::error(400, format!("Expected {{\"x\": <number>, \"y\": <number>}}: {}", e)),
+        },
+        "navigate" => match serde_json::from_slice::<GoToBody>(&req.body) {
+            Ok(body) => Command::Navigate { goals: vec![(body.x, body.y)] },
+            Err(e) => return Response::error(400, format!("Expected {{\"x\": <number>, \"y\": <number>}}: {}", e)),
+        },
+        "path" => match serde_json::from_slice::<PathBody>(&req.body) {
+            Ok(body) => Command::FollowPath { waypoints: body.waypoints, looping: body.looping },
+            Err(e) => return Response::error(400, format!("Expected {{\"waypoints\": [[x, y], ...]}}: {}", e)),
+        },
+        _ => return Response::error(404, format!("Unknown command: {}", action)),
+    };
+
+    match ctx.control.execute(&target, cmd, &operator) {
+        Ok(sent) => {
+            let mut body = serde_json::json!({ "ok": true, "robots": sent.robots, "operator": operator.name });
+            if let Some(mission) = sent.mission {
+                body["mission"] = mission.into();
+            }
+            Response::json(200, &body)
+        }
+        Err(e) => command_error(e),
+    }
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        503 => "Service Unavailable",
+        _ => "Method Not Allowed",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+// Robot IDs like "Bot A" arrive as "Bot%20A"
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(b) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
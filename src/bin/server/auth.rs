@@ -0,0 +1,101 @@
+// Robot client authentication with per-robot pre-shared keys.
+//
+// The credentials file is a JSON object mapping robot ID to key:
+//   { "Bot A": "correct-horse", "Bot B": "battery-staple" }
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// A host with this many failed handshakes within FAILURE_WINDOW is turned away
+const MAX_FAILURES: usize = 5;
+const FAILURE_WINDOW: Duration = Duration::from_secs(60);
+
+pub struct Credentials {
+    // None = authentication disabled (any ID accepted)
+    keys: Option<HashMap<String, String>>,
+}
+
+impl Credentials {
+    pub fn disabled() -> Self {
+        Self { keys: None }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let keys: HashMap<String, String> =
+            serde_json::from_str(&text).map_err(|e| format!("Invalid credentials file {}: {}", path, e))?;
+        Ok(Self { keys: Some(keys) })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    pub fn verify(&self, id: &str, key: &str) -> Result<(), String> {
+        let Some(keys) = &self.keys else { return Ok(()) };
+        match keys.get(id) {
+            Some(expected) if constant_time_eq(expected.as_bytes(), key.as_bytes()) => Ok(()),
+            Some(_) => Err(format!("Invalid key for robot '{}'", id)),
+            None => Err(format!("Unknown robot ID '{}'", id)),
+        }
+    }
+}
+
+// Compare without leaking how many leading bytes matched
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Failed key checks per peer address. Every connection has its own thread, so
+// a per-connection delay would not slow down guessing in parallel; instead a
+// host that keeps failing is refused before its Hello is read.
+#[derive(Default)]
+pub struct FailedAttempts {
+    by_ip: Mutex<HashMap<IpAddr, VecDeque<Instant>>>,
+}
+
+impl FailedAttempts {
+    pub fn record(&self, ip: IpAddr) {
+        let mut by_ip = self.by_ip.lock().unwrap();
+        by_ip.entry(ip).or_default().push_back(Instant::now());
+        for failures in by_ip.values_mut() {
+            while failures.front().is_some_and(|t| t.elapsed() > FAILURE_WINDOW) {
+                failures.pop_front();
+            }
+            // Only the last MAX_FAILURES matter
+            while failures.len() > MAX_FAILURES {
+                failures.pop_front();
+            }
+        }
+        by_ip.retain(|_, failures| !failures.is_empty());
+    }
+
+    pub fn locked_out(&self, ip: IpAddr) -> bool {
+        let by_ip = self.by_ip.lock().unwrap();
+        by_ip
+            .get(&ip)
+            .is_some_and(|failures| failures.iter().filter(|t| t.elapsed() <= FAILURE_WINDOW).count() >= MAX_FAILURES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_failures_lock_out_that_host_only() {
+        let attempts = FailedAttempts::default();
+        let (guesser, other): (IpAddr, IpAddr) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        for _ in 0..MAX_FAILURES - 1 {
+            attempts.record(guesser);
+        }
+        assert!(!attempts.locked_out(guesser));
+        attempts.record(guesser);
+        assert!(attempts.locked_out(guesser));
+        assert!(!attempts.locked_out(other));
+    }
+}
@@ -0,0 +1,293 @@
+// Per-robot connection handling: handshake, writer thread and telemetry reader.
+use crate::auth::{Credentials, FailedAttempts};
+use crate::delivery::{Outbox, SharedOutbox, RETRANSMIT_INTERVAL};
+use crate::link::LinkStats;
+use crate::udp::UdpHub;
+use crate::{RobotData, SharedRobots};
+use assignment2::codec::{Codec, CodecError};
+use assignment2::transport::NetStream;
+use assignment2::{now_ms, ClientMessage, Hello, ServerMessage, Welcome};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::BufReader;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const PING_INTERVAL: Duration = Duration::from_secs(1); // Clock offset and latency probes
+
+// Everything a connection handler needs, cloned per connection
+#[derive(Clone)]
+pub struct ClientContext {
+    pub max_frame: usize,
+    pub robots: SharedRobots,
+    pub credentials: Arc<Credentials>,
+    // Hosts that keep failing the key check are refused
+    pub failed: Arc<FailedAttempts>,
+    // Set on the server, overriding the priority declared in the Hello
+    pub priorities: Arc<HashMap<String, u8>>,
+    pub udp: Option<UdpHub>,
+    pub tx_log: mpsc::Sender<String>,
+    // IDs with a live connection, reserved as soon as the handshake succeeds.
+    // Robots only appear in `robots` with their first telemetry, too late to
+    // turn away a second connection using the same ID.
+    pub connected: Arc<Mutex<HashSet<String>>>,
+}
+
+// A robot ID held by one connection, released when the connection ends
+struct Reservation {
+    id: String,
+    connected: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Reservation {
+    fn take(id: &str, connected: &Arc<Mutex<HashSet<String>>>) -> Result<Self, String> {
+        if !connected.lock().unwrap().insert(id.to_string()) {
+            return Err(format!("Robot '{}' is already connected", id));
+        }
+        Ok(Reservation { id: id.to_string(), connected: connected.clone() })
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.connected.lock().unwrap().remove(&self.id);
+    }
+}
+
+// Reads the Hello and checks the robot's key. Returns the accepted Hello and
+// the reservation of its ID.
+// A verified client certificate (mutual TLS) replaces the key check, but the
+// Hello must then name the robot the certificate was issued to.
+fn authenticate(reader: &mut BufReader<NetStream>, ctx: &ClientContext) -> Result<(Hello, Reservation), String> {
+    // The handshake is always JSON, whatever codec is negotiated
+    let hello = match Codec::Json.read::<ClientMessage>(reader, ctx.max_frame) {
+        Ok(Some(ClientMessage::Hello(hello))) => hello,
+        Ok(Some(_)) => return Err("Expected Hello as first message".into()),
+        Ok(None) => return Err("Connection closed before handshake".into()),
+        Err(CodecError::Decode(e)) => return Err(format!("Malformed handshake: {}", e)),
+        Err(e) => return Err(format!("Handshake failed: {}", e)),
+    };
+
+    let checked = match reader.get_ref().peer_identity() {
+        Some(cert_id) if cert_id != hello.id => Err(format!("Certificate is for '{}', not '{}'", cert_id, hello.id)),
+        Some(_) => Ok(()),
+        None => ctx.credentials.verify(&hello.id, &hello.key),
+    };
+    if checked.is_err()
+        && let Ok(peer) = reader.get_ref().peer_addr()
+    {
+        ctx.failed.record(peer.ip());
+    }
+    checked?;
+    hello.footprint.validate()?;
+    let reservation = Reservation::take(&hello.id, &ctx.connected)?;
+    Ok((hello, reservation))
+}
+
+pub fn handle_client(stream: NetStream, ctx: ClientContext) {
+    let ClientContext { max_frame, ref robots, ref tx_log, .. } = ctx;
+    let peer = stream.peer_addr().unwrap();
+    let peer_addr = peer.to_string();
+    let _ = tx_log.send(format!("New connection: {}", peer_addr));
+
+    // Split stream for full-duplex
+    let stream_read = stream.try_clone().expect("Failed to clone stream");
+    let mut stream_write = stream;
+
+    if ctx.failed.locked_out(peer.ip()) {
+        let _ = tx_log.send(format!("AUTH REFUSED from {}: too many failed attempts", peer_addr));
+        let reason = "Too many failed attempts, try again later".to_string();
+        let _ = Codec::Json.write(&mut stream_write, &ServerMessage::Rejected(reason));
+        return;
+    }
+
+    // 0. HANDSHAKE: nothing else is accepted until the robot has authenticated
+    let _ = stream_read.set_read_timeout(Some(HANDSHAKE_TIMEOUT));
+    let mut reader = BufReader::new(stream_read);
+    // Held until the end of this function, after the robot has been removed
+    let (hello, _reservation) = match authenticate(&mut reader, &ctx) {
+        Ok(accepted) => accepted,
+        Err(reason) => {
+            let _ = tx_log.send(format!("AUTH FAILED from {}: {}", peer_addr, reason));
+            let _ = Codec::Json.write(&mut stream_write, &ServerMessage::Rejected(reason));
+            return;
+        }
+    };
+    let _ = reader.get_ref().set_read_timeout(None);
+    let robot_id = hello.id;
+    let codec = hello.codec;
+    let footprint = hello.footprint;
+    let priority = ctx.priorities.get(&robot_id).copied().unwrap_or(hello.priority);
+    let wants_neighbours = hello.neighbours;
+
+    // Telemetry over UDP if the robot asked for it and we are listening.
+    // Datagrams are plaintext, so a TLS connection keeps its telemetry on TCP.
+    let udp_session = match (&ctx.udp, hello.udp) {
+        (Some(_), true) if stream_write.is_tls() => {
+            let _ = tx_log.send(format!("Refused UDP telemetry for {}: the connection uses TLS", robot_id));
+            None
+        }
+        (Some(hub), true) => Some(hub.register(&robot_id, codec, peer.ip())),
+        _ => None,
+    };
+    let welcome = Welcome { codec, udp: udp_session, priority };
+    if Codec::Json.write(&mut stream_write, &ServerMessage::Welcome(welcome)).is_err() {
+        return;
+    }
+    let _ = tx_log.send(format!(
+        "Authenticated {} as {} ({:?}{}, priority {}{})",
+        peer_addr,
+        robot_id,
+        codec,
+        if udp_session.is_some() { ", UDP telemetry" } else { "" },
+        priority,
+        if priority != hello.priority { " set by server" } else { "" }
+    ));
+
+    // Channel for Server -> Client messages
+    let (tx_cmd, rx_cmd) = mpsc::channel::<ServerMessage>();
+
+    // Commands awaiting an Ack, shared by the writer, the reader and the GUI
+    let outbox: SharedOutbox = Arc::new(Mutex::new(Outbox::default()));
+
+    // 1. WRITER THREAD: Sends commands to this client
+    let log_clone_write = tx_log.clone();
+    let peer_addr_clone = peer_addr.clone();
+    let outbox_write = outbox.clone();
+    let robot_id_write = robot_id.clone();
+
+    thread::spawn(move || {
+        let mut last_ping: Option<Instant> = None;
+        // Ends when the channel closes
+        'writer: loop {
+            let mut batch = match rx_cmd.recv_timeout(RETRANSMIT_INTERVAL / 5) {
+                Ok(msg) if msg.needs_ack() => vec![outbox_write.lock().unwrap().track(msg)],
+                Ok(msg) => vec![msg],
+                Err(RecvTimeoutError::Timeout) => Vec::new(),
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            let mut outbox = outbox_write.lock().unwrap();
+            batch.extend(outbox.retransmit());
+            for msg_id in outbox.newly_overdue_stops() {
+                let _ = log_clone_write.send(format!(
+                    "ALARM: {} has not acknowledged ForceStop #{} (retransmitting)",
+                    robot_id_write, msg_id
+                ));
+            }
+            drop(outbox);
+            if last_ping.is_none_or(|t| t.elapsed() >= PING_INTERVAL) {
+                last_ping = Some(Instant::now());
+                batch.push(ServerMessage::Ping(now_ms()));
+            }
+
+            for msg in batch {
+                if codec.write(&mut stream_write, &msg).is_err() {
+                    break 'writer; // Client disconnected
+                }
+            }
+        }
+        let _ = log_clone_write.send(format!("Writer thread ended for {}", peer_addr_clone));
+    });
+
+    // 2. READER THREAD (Current thread): Receives telemetry
+    loop {
+        match codec.read::<ClientMessage>(&mut reader, max_frame) {
+            Ok(None) => break, // EOF
+            Ok(Some(ClientMessage::Telemetry(state))) => {
+                // A connection may only report for the robot it authenticated as
+                if state.id != robot_id {
+                    let _ = tx_log.send(format!(
+                        "Dropped telemetry for '{}' from {} (authenticated as '{}')",
+                        state.id, peer_addr, robot_id
+                    ));
+                    continue;
+                }
+                let mut guard = robots.lock().unwrap();
+                let id = state.id.clone();
+
+                let entry = guard.entry(id.clone()).or_insert_with(|| {
+                    let _ = tx_log.send(format!("Registered Robot: {}", id));
+                    RobotData {
+                        state: state.clone(),
+                        footprint: footprint.clone(),
+                        priority,
+                        neighbours: wants_neighbours,
+                        trail: VecDeque::new(),
+                        last_seen: std::time::Instant::now(),
+                        tx_to_client: tx_cmd.clone(),
+                        link: LinkStats::default(),
+                        outbox: outbox.clone(),
+                        mission: None,
+                    }
+                });
+                entry.update(state);
+            },
+            Ok(Some(ClientMessage::Pong(pong))) => {
+                let now = now_ms();
+                if let Some(robot) = robots.lock().unwrap().get_mut(&robot_id) {
+                    robot.link.clock_sample(&pong, now);
+                }
+            },
+            Ok(Some(ClientMessage::Ack(msg_id))) => {
+                let delivery = outbox.lock().unwrap().ack(msg_id);
+                // Stop acknowledgements are the evidence that every stop was received
+                if let Some(d) = delivery
+                    && matches!(d.command, ServerMessage::ForceStop)
+                {
+                    let _ = tx_log.send(format!(
+                        "{} acknowledged ForceStop #{} after {} ms ({} attempt{})",
+                        robot_id,
+                        d.msg_id,
+                        d.latency.as_millis(),
+                        d.attempts,
+                        if d.attempts == 1 { "" } else { "s" }
+                    ));
+                }
+            },
+            Ok(Some(ClientMessage::MissionProgress(progress))) => {
+                if let Some(robot) = robots.lock().unwrap().get_mut(&robot_id)
+                    && let Some(mission) = robot.mission.as_mut().filter(|m| m.id == progress.mission)
+                {
+                    let _ = tx_log.send(mission.update(&robot_id, &progress));
+                }
+            },
+            Ok(Some(ClientMessage::Disconnect(id))) => {
+                let _ = tx_log.send(format!("Robot {} sent disconnect.", id));
+                break;
+            },
+            Ok(Some(ClientMessage::Hello(_))) => {
+                let _ = tx_log.send(format!("Ignored repeated Hello from {}", peer_addr));
+            },
+            Err(CodecError::Decode(e)) => {
+                let _ = tx_log.send(format!("Decode Error from {}: {}", peer_addr, e));
+            }
+            Err(e @ CodecError::FrameTooLarge { .. }) => {
+                let _ = tx_log.send(format!("Closing {} ({}): {}", peer_addr, robot_id, e));
+                break;
+            }
+            Err(CodecError::Io(_)) => break,
+        }
+    }
+
+    // Cleanup
+    let unacked = outbox.lock().unwrap().oldest_stop();
+    if let Some(age) = unacked {
+        let _ = tx_log.send(format!(
+            "ALARM: {} disconnected without acknowledging a ForceStop ({} ms)",
+            robot_id,
+            age.as_millis()
+        ));
+    }
+    if let (Some(hub), Some(session)) = (&ctx.udp, &udp_session) {
+        hub.unregister(session);
+    }
+    // Only this connection's own entry, recognised by its outbox
+    let mut guard = robots.lock().unwrap();
+    if guard.get(&robot_id).is_some_and(|r| Arc::ptr_eq(&r.outbox, &outbox)) {
+        guard.remove(&robot_id);
+        let _ = tx_log.send(format!("Robot {} removed from state.", robot_id));
+    }
+}
@@ -0,0 +1,268 @@
+// Fleet commands shared by the GUI buttons and the HTTP control API.
+// Every command goes out through the per-robot `tx_to_client` channels.
+use crate::map::WorkspaceMap;
+use crate::mission::Mission;
+use crate::planner::Grid;
+use crate::operators::{Operator, Role};
+use crate::SharedRobots;
+use assignment2::{ServerMessage, BOUNDARY_HEIGHT, BOUNDARY_WIDTH};
+use std::sync::{mpsc, Arc, Mutex};
+
+#[derive(Clone, Debug)]
+pub enum Command {
+    Stop,
+    Resume,
+    SetSpeedLimit(f32),
+    // Right of way in the safety checks and traffic management; the robot is told
+    SetPriority(u8),
+    // Missions, for a single robot
+    GoTo { x: f32, y: f32 },
+    FollowPath { waypoints: Vec<(f32, f32)>, looping: bool },
+    // Planned around the map's obstacles through each goal in turn, then sent as a FollowPath
+    Navigate { goals: Vec<(f32, f32)> },
+    // Ends a mission if the robot is still on it; the robot stops
+    CancelMission(u64),
+}
+
+// What a command reached
+#[derive(Debug)]
+pub struct Sent {
+    pub robots: usize,
+    // ID given to a GoTo / FollowPath
+    pub mission: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Target {
+    All,
+    Robot(String),
+}
+
+#[derive(Debug)]
+pub enum CommandError {
+    UnknownRobot(String),
+    UnknownJob(u64),
+    Invalid(String),
+    Forbidden(String),
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CommandError::UnknownRobot(id) => write!(f, "Unknown robot: {}", id),
+            CommandError::UnknownJob(id) => write!(f, "Unknown job: #{}", id),
+            CommandError::Invalid(msg) | CommandError::Forbidden(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FleetControl {
+    robots: SharedRobots,
+    tx_log: mpsc::Sender<String>,
+    global_speed_limit: Arc<Mutex<f32>>,
+    // Set by a fleet-wide stop; only a safety supervisor may resume until it is cleared
+    estop_latched: Arc<Mutex<bool>>,
+    next_mission: Arc<Mutex<u64>>,
+    map: Arc<WorkspaceMap>,
+}
+
+impl Command {
+    // Least privileged role allowed to issue this command
+    pub fn required_role(&self, estop_latched: bool) -> Role {
+        match self {
+            // A mission does not clear a stop, so it needs no more than the operator role
+            Command::Stop
+            | Command::CancelMission(_)
+            | Command::GoTo { .. }
+            | Command::FollowPath { .. }
+            | Command::Navigate { .. } => Role::Operator,
+            Command::Resume if estop_latched => Role::SafetySupervisor,
+            Command::Resume => Role::Operator,
+            Command::SetSpeedLimit(_) | Command::SetPriority(_) => Role::SafetySupervisor,
+        }
+    }
+}
+
+impl FleetControl {
+    pub fn new(robots: SharedRobots, tx_log: mpsc::Sender<String>, map: WorkspaceMap) -> Self {
+        Self {
+            robots,
+            tx_log,
+            global_speed_limit: Arc::new(Mutex::new(100.0)),
+            estop_latched: Arc::new(Mutex::new(false)),
+            next_mission: Arc::new(Mutex::new(1)),
+            map: Arc::new(map),
+        }
+    }
+
+    pub fn map(&self) -> &WorkspaceMap {
+        &self.map
+    }
+
+    pub fn robots(&self) -> &SharedRobots {
+        &self.robots
+    }
+
+    pub fn global_speed_limit(&self) -> f32 {
+        *self.global_speed_limit.lock().unwrap()
+    }
+
+    pub fn estop_latched(&self) -> bool {
+        *self.estop_latched.lock().unwrap()
+    }
+
+    pub fn allowed(&self, cmd: &Command, operator: &Operator) -> bool {
+        operator.role >= cmd.required_role(self.estop_latched())
+    }
+
+    // Sends `cmd` to the target robot(s) if `operator` may issue it, and logs who did.
+    // Returns how many robots were commanded, and the ID of a new mission.
+    pub fn execute(&self, target: &Target, cmd: Command, operator: &Operator) -> Result<Sent, CommandError> {
+        let stamp = format!("{} ({}, {})", operator.name, operator.role, operator.source);
+        if !self.allowed(&cmd, operator) {
+            let required = cmd.required_role(self.estop_latched());
+            let _ = self.tx_log.send(format!("[{}] DENIED {:?}: requires {}", stamp, cmd, required));
+            return Err(CommandError::Forbidden(format!("{:?} requires the {} role", cmd, required)));
+        }
+
+        let (msg, what) = match cmd {
+            Command::Stop => (ServerMessage::ForceStop, "STOP".to_string()),
+            Command::Resume => (ServerMessage::Resume, "RESUME".to_string()),
+            Command::SetSpeedLimit(limit) => {
+                if !(0.0..=200.0).contains(&limit) {
+                    return Err(CommandError::Invalid(format!("Speed limit {} outside 0-200", limit)));
+                }
+                (ServerMessage::SetSpeedLimit(limit), format!("SPEED LIMIT {:.0}", limit))
+            }
+            Command::SetPriority(priority) => (ServerMessage::SetPriority(priority), format!("PRIORITY {}", priority)),
+            Command::CancelMission(mission) => {
+                (ServerMessage::CancelMission(mission), format!("CANCEL MISSION #{}", mission))
+            }
+            Command::GoTo { x, y } => return self.dispatch(target, vec![(x, y)], false, &stamp, operator),
+            Command::FollowPath { ref waypoints, looping } => {
+                return self.dispatch(target, waypoints.clone(), looping, &stamp, operator);
+            }
+            Command::Navigate { ref goals } => {
+                let Target::Robot(id) = target else {
+                    return Err(CommandError::Invalid("Missions are sent to one robot at a time".into()));
+                };
+                let waypoints = self.plan(id, goals)?.concat();
+                let _ = self.tx_log.send(format!(
+                    "[{}] Planned {} around the map through {} goal(s): {} waypoint(s)",
+                    stamp,
+                    id,
+                    goals.len(),
+                    waypoints.len()
+                ));
+                return self.dispatch(target, waypoints, false, &stamp, operator);
+            }
+        };
+
+        let mut guard = self.robots.lock().unwrap();
+        let count = match target {
+            Target::All => {
+                // Novel Feature: Server-side Global Speed Throttle
+                match cmd {
+                    Command::SetSpeedLimit(limit) => *self.global_speed_limit.lock().unwrap() = limit,
+                    Command::Stop => *self.estop_latched.lock().unwrap() = true,
+                    Command::Resume => *self.estop_latched.lock().unwrap() = false,
+                    Command::GoTo { .. }
+                    | Command::FollowPath { .. }
+                    | Command::Navigate { .. }
+                    | Command::CancelMission(_)
+                    | Command::SetPriority(_) => {}
+                }
+                for robot in guard.values_mut() {
+                    if let Command::SetPriority(priority) = cmd {
+                        robot.priority = priority;
+                    }
+                    let _ = robot.tx_to_client.send(msg.clone());
+                }
+                guard.len()
+            }
+            Target::Robot(id) => {
+                let robot = guard.get_mut(id).ok_or_else(|| CommandError::UnknownRobot(id.clone()))?;
+                if let Command::SetPriority(priority) = cmd {
+                    robot.priority = priority;
+                }
+                let _ = robot.tx_to_client.send(msg);
+                1
+            }
+        };
+
+        let whom = match target {
+            Target::All => "GLOBAL".to_string(),
+            Target::Robot(id) => id.clone(),
+        };
+        let _ = self.tx_log.send(format!("[{}] Sent {} {} command", stamp, whom, what));
+        Ok(Sent { robots: count, mission: None })
+    }
+
+    // Sends a GoTo (a single non-looping waypoint) or FollowPath under a new
+    // mission ID, and records the mission on the robot
+    fn dispatch(
+        &self,
+        target: &Target,
+        waypoints: Vec<(f32, f32)>,
+        looping: bool,
+        stamp: &str,
+        operator: &Operator,
+    ) -> Result<Sent, CommandError> {
+        let Target::Robot(id) = target else {
+            return Err(CommandError::Invalid("Missions are sent to one robot at a time".into()));
+        };
+        if waypoints.is_empty() {
+            return Err(CommandError::Invalid("A path needs at least one waypoint".into()));
+        }
+        let inside = |&&(x, y): &&(f32, f32)| (0.0..=BOUNDARY_WIDTH).contains(&x) && (0.0..=BOUNDARY_HEIGHT).contains(&y);
+        if let Some((x, y)) = waypoints.iter().find(|p| !inside(p)) {
+            return Err(CommandError::Invalid(format!("Waypoint ({}, {}) is outside the workspace", x, y)));
+        }
+        if let Some((&(x, y), what)) = waypoints.iter().find_map(|p| Some((p, self.map.zone_at(*p)?))) {
+            return Err(CommandError::Invalid(format!("Waypoint ({}, {}) is inside {}", x, y, what)));
+        }
+
+        let mut guard = self.robots.lock().unwrap();
+        let robot = guard.get_mut(id).ok_or_else(|| CommandError::UnknownRobot(id.clone()))?;
+        let mission = {
+            let mut next = self.next_mission.lock().unwrap();
+            *next += 1;
+            *next - 1
+        };
+        let msg = match waypoints.as_slice() {
+            [(x, y)] if !looping => ServerMessage::GoTo { mission, x: *x, y: *y },
+            _ => ServerMessage::FollowPath { mission, waypoints: waypoints.clone(), looping },
+        };
+        let _ = robot.tx_to_client.send(msg);
+        let record = Mission::new(mission, waypoints, looping, &operator.name);
+        let _ = self.tx_log.send(format!("[{}] Sent {} {} (mission #{})", stamp, id, record.describe(), mission));
+        robot.mission = Some(record);
+        Ok(Sent { robots: 1, mission: Some(mission) })
+    }
+
+    // Plans a path for the robot from where it is now through each goal in
+    // turn, around the map's obstacles and keep-out zones. One leg per goal,
+    // each ending on its goal.
+    pub fn plan(&self, id: &str, goals: &[(f32, f32)]) -> Result<Vec<Vec<(f32, f32)>>, CommandError> {
+        if let Some((&(x, y), what)) = goals.iter().find_map(|p| Some((p, self.map.zone_at(*p)?))) {
+            return Err(CommandError::Invalid(format!("Goal ({}, {}) is inside {}", x, y, what)));
+        }
+        let (start, reach) = {
+            let guard = self.robots.lock().unwrap();
+            let robot = guard.get(id).ok_or_else(|| CommandError::UnknownRobot(id.to_string()))?;
+            let pos = robot.estimated_position().0;
+            ((pos.x, pos.y), robot.footprint.reach())
+        };
+        let grid = Grid::new(&self.map, reach);
+        let mut from = start;
+        goals
+            .iter()
+            .map(|&goal| {
+                let leg = grid.plan(from, goal).map_err(CommandError::Invalid)?;
+                from = goal;
+                Ok(leg)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,190 @@
+// Acknowledged delivery of safety-critical commands.
+//
+// The writer thread wraps every command that needs an ack in
+// ServerMessage::Reliable and keeps it here until the robot answers with
+// ClientMessage::Ack. Unanswered commands are sent again every
+// RETRANSMIT_INTERVAL; a stop still unanswered after ACK_DEADLINE raises an
+// alarm in the server UI.
+use assignment2::ServerMessage;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const RETRANSMIT_INTERVAL: Duration = Duration::from_millis(250);
+pub const ACK_DEADLINE: Duration = Duration::from_secs(1);
+
+struct Pending {
+    command: ServerMessage,
+    first_sent: Instant,
+    last_sent: Instant,
+    attempts: u32,
+    alarmed: bool,
+}
+
+// Proof of delivery for one command
+pub struct Delivery {
+    pub msg_id: u64,
+    pub command: ServerMessage,
+    pub latency: Duration,
+    pub attempts: u32,
+}
+
+#[derive(Default)]
+pub struct Outbox {
+    next_id: u64,
+    pending: BTreeMap<u64, Pending>,
+    last_stop_acked: Option<Instant>,
+}
+
+pub type SharedOutbox = Arc<Mutex<Outbox>>;
+
+impl Outbox {
+    // Registers a command and returns the envelope to send
+    pub fn track(&mut self, command: ServerMessage) -> ServerMessage {
+        self.next_id += 1;
+        let now = Instant::now();
+        self.pending.insert(
+            self.next_id,
+            Pending { command: command.clone(), first_sent: now, last_sent: now, attempts: 1, alarmed: false },
+        );
+        ServerMessage::Reliable { msg_id: self.next_id, command: Box::new(command) }
+    }
+
+    // None if the ID is unknown or was already acknowledged
+    pub fn ack(&mut self, msg_id: u64) -> Option<Delivery> {
+        let pending = self.pending.remove(&msg_id)?;
+        if is_stop(&pending.command) {
+            self.last_stop_acked = Some(Instant::now());
+        }
+        Some(Delivery {
+            msg_id,
+            command: pending.command,
+            latency: pending.first_sent.elapsed(),
+            attempts: pending.attempts,
+        })
+    }
+
+    // Envelopes due for another attempt
+    pub fn retransmit(&mut self) -> Vec<ServerMessage> {
+        let now = Instant::now();
+        self.pending
+            .iter_mut()
+            .filter(|(_, p)| now.duration_since(p.last_sent) >= RETRANSMIT_INTERVAL)
+            .map(|(&msg_id, p)| {
+                p.last_sent = now;
+                p.attempts += 1;
+                ServerMessage::Reliable { msg_id, command: Box::new(p.command.clone()) }
+            })
+            .collect()
+    }
+
+    // Stops that just went past the deadline; each is reported once
+    pub fn newly_overdue_stops(&mut self) -> Vec<u64> {
+        self.pending
+            .iter_mut()
+            .filter(|(_, p)| !p.alarmed && is_stop(&p.command) && p.first_sent.elapsed() >= ACK_DEADLINE)
+            .map(|(&msg_id, p)| {
+                p.alarmed = true;
+                msg_id
+            })
+            .collect()
+    }
+
+    // A stop is being delivered, or one was acknowledged after `since`
+    pub fn stop_in_effect(&self, since: Instant) -> bool {
+        self.oldest_stop().is_some() || self.last_stop_acked.is_some_and(|t| t >= since)
+    }
+
+    // How long the oldest unacknowledged stop has been waiting
+    pub fn oldest_stop(&self) -> Option<Duration> {
+        self.pending.values().filter(|p| is_stop(&p.command)).map(|p| p.first_sent.elapsed()).max()
+    }
+
+    // Same, but only once the ack deadline has passed
+    pub fn overdue_stop(&self) -> Option<Duration> {
+        self.oldest_stop().filter(|age| *age >= ACK_DEADLINE)
+    }
+}
+
+fn is_stop(command: &ServerMessage) -> bool {
+    matches!(command, ServerMessage::ForceStop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Makes a pending command look as if it was sent `by` earlier
+    fn backdate(outbox: &mut Outbox, msg_id: u64, by: Duration) {
+        let p = outbox.pending.get_mut(&msg_id).unwrap();
+        p.first_sent -= by;
+        p.last_sent -= by;
+    }
+
+    fn msg_id(envelope: &ServerMessage) -> u64 {
+        match envelope {
+            ServerMessage::Reliable { msg_id, .. } => *msg_id,
+            other => panic!("not an envelope: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ack_removes_the_entry_and_reports_delay_and_attempts() {
+        let mut outbox = Outbox::default();
+        let id = msg_id(&outbox.track(ServerMessage::ForceStop));
+        backdate(&mut outbox, id, RETRANSMIT_INTERVAL);
+        assert_eq!(outbox.retransmit().len(), 1);
+
+        let delivery = outbox.ack(id).unwrap();
+        assert_eq!(delivery.msg_id, id);
+        assert!(matches!(delivery.command, ServerMessage::ForceStop));
+        assert!(delivery.latency >= RETRANSMIT_INTERVAL);
+        assert_eq!(delivery.attempts, 2);
+        // A repeated Ack finds nothing
+        assert!(outbox.ack(id).is_none());
+        assert!(outbox.oldest_stop().is_none());
+    }
+
+    #[test]
+    fn retransmits_only_after_the_interval() {
+        let mut outbox = Outbox::default();
+        let id = msg_id(&outbox.track(ServerMessage::Resume));
+        assert!(outbox.retransmit().is_empty());
+
+        backdate(&mut outbox, id, RETRANSMIT_INTERVAL);
+        let resent = outbox.retransmit();
+        assert_eq!(resent.iter().map(msg_id).collect::<Vec<_>>(), vec![id]);
+        // The clock starts again from the retransmission
+        assert!(outbox.retransmit().is_empty());
+    }
+
+    #[test]
+    fn overdue_stops_are_reported_once() {
+        let mut outbox = Outbox::default();
+        let stop = msg_id(&outbox.track(ServerMessage::ForceStop));
+        let resume = msg_id(&outbox.track(ServerMessage::Resume));
+        assert!(outbox.newly_overdue_stops().is_empty());
+        assert!(outbox.overdue_stop().is_none());
+
+        backdate(&mut outbox, stop, ACK_DEADLINE);
+        backdate(&mut outbox, resume, ACK_DEADLINE);
+        assert_eq!(outbox.newly_overdue_stops(), vec![stop]);
+        assert!(outbox.newly_overdue_stops().is_empty());
+        assert!(outbox.overdue_stop().is_some());
+    }
+
+    #[test]
+    fn stop_in_effect_until_telemetry_after_the_ack() {
+        let mut outbox = Outbox::default();
+        let before = Instant::now();
+        assert!(!outbox.stop_in_effect(before));
+
+        let id = msg_id(&outbox.track(ServerMessage::ForceStop));
+        assert!(outbox.stop_in_effect(before));
+        outbox.ack(id);
+        // Acknowledged after the latest telemetry, which cannot show it yet
+        assert!(outbox.stop_in_effect(before));
+        // Telemetry received since the ack speaks for itself
+        assert!(!outbox.stop_in_effect(Instant::now()));
+    }
+}
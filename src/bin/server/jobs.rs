@@ -0,0 +1,560 @@
+// Job queue: transport jobs (pick up at A, drop off at B) handed out
+// automatically across the fleet.
+//
+// An allocator thread gives each queued job, oldest first, to the nearest
+// idle robot as a FollowPath mission, planned around the map from the robot
+// to the pickup and on to the drop-off, and follows it through the robot's
+// MissionProgress reports. A job goes back in the queue when its robot
+// disconnects, drops the mission, or stays stopped for longer than
+// STOPPED_TOO_LONG. If the load was already picked up, the new pickup point is
+// where the robot was last seen.
+use crate::control::{Command, CommandError, FleetControl, Target};
+use crate::operators::{Operator, Role};
+use crate::safety::STALE_AFTER;
+use assignment2::{MissionStatus, BOUNDARY_HEIGHT, BOUNDARY_WIDTH};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ALLOCATE_INTERVAL: Duration = Duration::from_millis(500);
+// A robot stopped this long on a job loses it to another robot
+pub const STOPPED_TOO_LONG: Duration = Duration::from_secs(10);
+// Finished jobs beyond this many are forgotten, oldest first
+const MAX_FINISHED: usize = 50;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    ToPickup,
+    ToDropoff,
+    Done,
+    Cancelled,
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            JobStatus::Queued => "queued",
+            JobStatus::ToPickup => "to pickup",
+            JobStatus::ToDropoff => "to drop-off",
+            JobStatus::Done => "done",
+            JobStatus::Cancelled => "cancelled",
+        })
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Job {
+    pub id: u64,
+    pub pickup: (f32, f32),
+    pub dropoff: (f32, f32),
+    pub status: JobStatus,
+    // Robot and mission carrying the job out
+    pub robot: Option<String>,
+    pub mission: Option<u64>,
+    pub created_by: String,
+    // Times the job went back in the queue
+    pub reassigned: u32,
+    #[serde(skip)]
+    created: Instant,
+    // Robot that last gave the job up; others are preferred for it
+    #[serde(skip)]
+    given_up_by: Option<String>,
+    // Since when the assigned robot has been stopped
+    #[serde(skip)]
+    stopped_since: Option<Instant>,
+    #[serde(skip)]
+    last_position: Option<(f32, f32)>,
+    // Waypoints up to and including the pickup in the current mission
+    #[serde(skip)]
+    pickup_at: usize,
+    // Latest reason the job could not be sent, logged once
+    #[serde(skip)]
+    last_error: Option<String>,
+}
+
+impl Job {
+    pub fn is_open(&self) -> bool {
+        !matches!(self.status, JobStatus::Done | JobStatus::Cancelled)
+    }
+
+    fn is_assigned(&self) -> bool {
+        matches!(self.status, JobStatus::ToPickup | JobStatus::ToDropoff)
+    }
+
+    // e.g. "(100, 100) -> (300, 200)"
+    pub fn describe(&self) -> String {
+        format!(
+            "({:.0}, {:.0}) -> ({:.0}, {:.0})",
+            self.pickup.0, self.pickup.1, self.dropoff.0, self.dropoff.1
+        )
+    }
+}
+
+#[derive(Default)]
+struct JobQueue {
+    next_id: u64,
+    jobs: Vec<Job>,
+}
+
+// What the allocator needs from each robot, copied out of the robots lock
+struct RobotView {
+    position: (f32, f32),
+    stopped: bool,
+    // ID and status of the latest mission
+    mission: Option<(u64, Option<MissionStatus>, usize)>,
+    idle: bool,
+}
+
+// Shared by the GUI, the HTTP API and the allocator thread. Lock order is
+// jobs, then robots: never lock the jobs while holding the robots.
+#[derive(Clone)]
+pub struct JobBoard {
+    queue: Arc<Mutex<JobQueue>>,
+    control: FleetControl,
+    tx_log: mpsc::Sender<String>,
+    // Dispatches jobs in its own name
+    allocator: Operator,
+}
+
+impl JobBoard {
+    pub fn new(control: FleetControl, tx_log: mpsc::Sender<String>) -> Self {
+        Self {
+            queue: Arc::new(Mutex::new(JobQueue { next_id: 1, jobs: Vec::new() })),
+            control,
+            tx_log,
+            allocator: Operator { name: "allocator".into(), role: Role::Operator, source: "jobs" },
+        }
+    }
+
+    // Open jobs first, then the finished ones, newest first
+    pub fn list(&self) -> Vec<Job> {
+        let mut jobs = self.queue.lock().unwrap().jobs.clone();
+        jobs.sort_by_key(|j| (!j.is_open(), if j.is_open() { j.id } else { u64::MAX - j.id }));
+        jobs
+    }
+
+    pub fn add(&self, pickup: (f32, f32), dropoff: (f32, f32), operator: &Operator) -> Result<u64, CommandError> {
+        if operator.role < Role::Operator {
+            return Err(CommandError::Forbidden(format!("Adding jobs requires the {} role", Role::Operator)));
+        }
+        let inside = |(x, y): (f32, f32)| (0.0..=BOUNDARY_WIDTH).contains(&x) && (0.0..=BOUNDARY_HEIGHT).contains(&y);
+        if let Some((x, y)) = [pickup, dropoff].into_iter().find(|&p| !inside(p)) {
+            return Err(CommandError::Invalid(format!("({}, {}) is outside the workspace", x, y)));
+        }
+        if let Some((&(x, y), what)) = [pickup, dropoff].iter().find_map(|p| Some((p, self.control.map().zone_at(*p)?))) {
+            return Err(CommandError::Invalid(format!("({}, {}) is inside {}", x, y, what)));
+        }
+
+        let mut queue = self.queue.lock().unwrap();
+        let id = queue.next_id;
+        queue.next_id += 1;
+        let job = Job {
+            id,
+            pickup,
+            dropoff,
+            status: JobStatus::Queued,
+            robot: None,
+            mission: None,
+            created_by: operator.name.clone(),
+            reassigned: 0,
+            created: Instant::now(),
+            given_up_by: None,
+            stopped_since: None,
+            last_position: None,
+            pickup_at: 1,
+            last_error: None,
+        };
+        let _ = self.tx_log.send(format!("[{} ({}, {})] Queued job #{} {}", operator.name, operator.role, operator.source, id, job.describe()));
+        queue.jobs.push(job);
+        Ok(id)
+    }
+
+    // Takes a job out of the queue, or off the robot carrying it out
+    pub fn cancel(&self, id: u64, operator: &Operator) -> Result<(), CommandError> {
+        if operator.role < Role::Operator {
+            return Err(CommandError::Forbidden(format!("Cancelling jobs requires the {} role", Role::Operator)));
+        }
+        let mut queue = self.queue.lock().unwrap();
+        let job = queue.jobs.iter_mut().find(|j| j.id == id).ok_or(CommandError::UnknownJob(id))?;
+        if !job.is_open() {
+            return Err(CommandError::Invalid(format!("Job #{} is already {}", id, job.status)));
+        }
+        if let (Some(robot), Some(mission)) = (&job.robot, job.mission) {
+            // The robot may have gone already; the job is cancelled either way
+            let _ = self.control.execute(&Target::Robot(robot.clone()), Command::CancelMission(mission), operator);
+        }
+        job.status = JobStatus::Cancelled;
+        let _ = self.tx_log.send(format!("[{} ({}, {})] Cancelled job #{}", operator.name, operator.role, operator.source, id));
+        Ok(())
+    }
+
+    pub fn spawn_allocator(&self) {
+        let board = self.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(ALLOCATE_INTERVAL);
+                board.allocate();
+            }
+        });
+    }
+
+    fn allocate(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        let robots: HashMap<String, RobotView> = self
+            .control
+            .robots()
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, r)| {
+                let pos = r.estimated_position().0;
+                let stale = r.data_age() > STALE_AFTER;
+                let view = RobotView {
+                    position: (pos.x, pos.y),
+                    stopped: !r.state.active || stale,
+                    mission: r.mission.as_ref().map(|m| (m.id, m.status, m.reached)),
+                    // A stopped robot would not set off, since missions never clear a stop
+                    idle: r.state.active && !stale && r.mission.as_ref().is_none_or(|m| !m.is_open()),
+                };
+                (id.clone(), view)
+            })
+            .collect();
+        // Nobody can move while the e-stop is latched, so nobody is stopped too long either
+        let latched = self.control.estop_latched();
+
+        for job in queue.jobs.iter_mut().filter(|j| j.is_assigned()) {
+            let robot_id = job.robot.clone().unwrap_or_default();
+            let Some(robot) = robots.get(&robot_id) else {
+                self.requeue(job, "robot disconnected");
+                continue;
+            };
+            job.last_position = Some(robot.position);
+            match robot.mission {
+                Some((mission, status, reached)) if Some(mission) == job.mission => match status {
+                    Some(MissionStatus::Completed) => {
+                        job.status = JobStatus::Done;
+                        let took = job.created.elapsed().as_secs_f32();
+                        let _ = self.tx_log.send(format!("Job #{} delivered by {} ({:.1} s after it was queued)", job.id, robot_id, took));
+                        continue;
+                    }
+                    Some(MissionStatus::Cancelled) => {
+                        self.requeue(job, "mission cancelled on the robot");
+                        continue;
+                    }
+                    _ if reached >= job.pickup_at && job.status == JobStatus::ToPickup => {
+                        job.status = JobStatus::ToDropoff;
+                        let _ = self.tx_log.send(format!("Job #{} picked up by {}", job.id, robot_id));
+                    }
+                    _ => {}
+                },
+                _ => {
+                    self.requeue(job, "mission replaced");
+                    continue;
+                }
+            }
+
+            if !robot.stopped || latched {
+                job.stopped_since = None;
+            } else if job.stopped_since.get_or_insert_with(Instant::now).elapsed() > STOPPED_TOO_LONG {
+                if let Some(mission) = job.mission {
+                    let _ = self.control.execute(&Target::Robot(robot_id.clone()), Command::CancelMission(mission), &self.allocator);
+                }
+                self.requeue(job, "robot stopped too long");
+            }
+        }
+
+        if latched {
+            return;
+        }
+        let mut busy: Vec<String> = queue.jobs.iter().filter(|j| j.is_assigned()).filter_map(|j| j.robot.clone()).collect();
+        for job in queue.jobs.iter_mut().filter(|j| j.status == JobStatus::Queued) {
+            // Nearest idle robot, preferring any other than the one that gave the job up
+            let distance = |(x, y): (f32, f32)| (x - job.pickup.0).hypot(y - job.pickup.1);
+            let nearest = robots
+                .iter()
+                .filter(|(id, r)| r.idle && !busy.contains(id))
+                .map(|(id, r)| (id, distance(r.position)))
+                .min_by(|a, b| {
+                    let gave_up = |id: &String| job.given_up_by.as_ref() == Some(id);
+                    (gave_up(a.0), a.1).partial_cmp(&(gave_up(b.0), b.1)).unwrap_or(std::cmp::Ordering::Equal)
+                });
+            let Some((robot_id, distance)) = nearest else {
+                break;
+            };
+
+            let legs = match self.control.plan(robot_id, &[job.pickup, job.dropoff]) {
+                Ok(legs) => legs,
+                Err(e) => {
+                    let error = format!("Job #{} not assigned to {}: {}", job.id, robot_id, e);
+                    if job.last_error.as_ref() != Some(&error) {
+                        let _ = self.tx_log.send(error.clone());
+                        job.last_error = Some(error);
+                    }
+                    continue;
+                }
+            };
+            job.pickup_at = legs[0].len();
+            let cmd = Command::FollowPath { waypoints: legs.concat(), looping: false };
+            match self.control.execute(&Target::Robot(robot_id.clone()), cmd, &self.allocator) {
+                Ok(sent) => {
+                    job.last_error = None;
+                    job.status = JobStatus::ToPickup;
+                    job.robot = Some(robot_id.clone());
+                    job.mission = sent.mission;
+                    job.stopped_since = None;
+                    busy.push(robot_id.clone());
+                    let _ = self.tx_log.send(format!("Job #{} assigned to {} ({:.0} px from the pickup)", job.id, robot_id, distance));
+                }
+                Err(e) => {
+                    let _ = self.tx_log.send(format!("Job #{} not assigned to {}: {}", job.id, robot_id, e));
+                }
+            }
+        }
+
+        // Forget the oldest finished jobs
+        let finished = queue.jobs.iter().filter(|j| !j.is_open()).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED);
+        queue.jobs.retain(|j| {
+            let drop = excess > 0 && !j.is_open();
+            if drop {
+                excess -= 1;
+            }
+            !drop
+        });
+    }
+
+    // Puts an assigned job back in the queue for another robot
+    fn requeue(&self, job: &mut Job, reason: &str) {
+        let mut line = format!("Job #{} back in the queue ({}: {})", job.id, job.robot.as_deref().unwrap_or("?"), reason);
+        if job.status == JobStatus::ToDropoff
+            && let Some(at) = job.last_position
+        {
+            job.pickup = at;
+            line += &format!(", load left at ({:.0}, {:.0})", at.0, at.1);
+        }
+        let _ = self.tx_log.send(line);
+        job.status = JobStatus::Queued;
+        job.given_up_by = job.robot.take();
+        job.mission = None;
+        job.stopped_since = None;
+        job.reassigned += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::WorkspaceMap;
+    use crate::RobotData;
+    use assignment2::{RobotState, ServerMessage};
+    use std::collections::VecDeque;
+
+    // Log lines go nowhere; every send ignores a closed channel
+    fn board() -> JobBoard {
+        let (tx_log, _) = mpsc::channel();
+        JobBoard::new(FleetControl::new(Default::default(), tx_log.clone(), WorkspaceMap::default()), tx_log)
+    }
+
+    fn operator() -> Operator {
+        Operator { name: "alice".into(), role: Role::Operator, source: "test" }
+    }
+
+    // Registers a robot at rest and returns what the server sends it
+    fn connect(board: &JobBoard, id: &str, (x, y): (f32, f32), active: bool) -> mpsc::Receiver<ServerMessage> {
+        let (tx, rx) = mpsc::channel();
+        let state = RobotState {
+            id: id.into(),
+            x,
+            y,
+            speed: 0.0,
+            angle: 0.0,
+            active,
+            color: (0, 0, 0),
+            seq: 0,
+            sent_at_ms: 0,
+            stopping_distance: 0.0,
+            model: Default::default(),
+        };
+        let robot = RobotData {
+            state,
+            footprint: Default::default(),
+            priority: 0,
+            neighbours: false,
+            trail: VecDeque::new(),
+            last_seen: Instant::now(),
+            tx_to_client: tx,
+            link: Default::default(),
+            outbox: Default::default(),
+            mission: None,
+        };
+        board.control.robots().lock().unwrap().insert(id.into(), robot);
+        rx
+    }
+
+    fn job(board: &JobBoard, id: u64) -> Job {
+        board.list().into_iter().find(|j| j.id == id).unwrap()
+    }
+
+    fn report(board: &JobBoard, robot: &str, status: MissionStatus, reached: usize) {
+        let mut robots = board.control.robots().lock().unwrap();
+        let mission = robots.get_mut(robot).unwrap().mission.as_mut().unwrap();
+        mission.status = Some(status);
+        mission.reached = reached;
+    }
+
+    #[test]
+    fn queued_jobs_go_to_the_nearest_idle_robot() {
+        let board = board();
+        let far = connect(&board, "far", (500.0, 300.0), true);
+        let near = connect(&board, "near", (150.0, 100.0), true);
+        let id = board.add((100.0, 100.0), (300.0, 200.0), &operator()).unwrap();
+        board.allocate();
+
+        let job = job(&board, id);
+        assert_eq!(job.status, JobStatus::ToPickup);
+        assert_eq!(job.robot.as_deref(), Some("near"));
+        match near.try_recv() {
+            Ok(ServerMessage::FollowPath { waypoints, looping: false, .. }) => {
+                assert_eq!(waypoints.last(), Some(&(300.0, 200.0)));
+                assert!(waypoints.contains(&(100.0, 100.0)));
+            }
+            other => panic!("expected a FollowPath, got {:?}", other),
+        }
+        assert!(far.try_recv().is_err());
+    }
+
+    #[test]
+    fn stopped_robots_are_never_given_jobs() {
+        let board = board();
+        let _stopped = connect(&board, "stopped", (100.0, 100.0), false);
+        let id = board.add((100.0, 100.0), (300.0, 200.0), &operator()).unwrap();
+        board.allocate();
+        assert_eq!(job(&board, id).status, JobStatus::Queued);
+
+        let _idle = connect(&board, "idle", (500.0, 300.0), true);
+        board.allocate();
+        assert_eq!(job(&board, id).robot.as_deref(), Some("idle"));
+    }
+
+    #[test]
+    fn a_busy_robot_gets_one_job_at_a_time() {
+        let board = board();
+        let _robot = connect(&board, "A", (100.0, 100.0), true);
+        let first = board.add((100.0, 100.0), (300.0, 200.0), &operator()).unwrap();
+        let second = board.add((120.0, 100.0), (300.0, 200.0), &operator()).unwrap();
+        board.allocate();
+        assert_eq!(job(&board, first).robot.as_deref(), Some("A"));
+        assert_eq!(job(&board, second).status, JobStatus::Queued);
+    }
+
+    #[test]
+    fn completing_the_mission_delivers_the_job() {
+        let board = board();
+        let _robot = connect(&board, "A", (100.0, 100.0), true);
+        let id = board.add((150.0, 100.0), (300.0, 200.0), &operator()).unwrap();
+        board.allocate();
+
+        let pickup_at = job(&board, id).pickup_at;
+        report(&board, "A", MissionStatus::InProgress, pickup_at);
+        board.allocate();
+        assert_eq!(job(&board, id).status, JobStatus::ToDropoff);
+
+        report(&board, "A", MissionStatus::Completed, pickup_at + 1);
+        board.allocate();
+        let job = job(&board, id);
+        assert_eq!(job.status, JobStatus::Done);
+        assert!(!job.is_open());
+    }
+
+    #[test]
+    fn a_job_goes_back_in_the_queue_when_its_robot_disconnects() {
+        let board = board();
+        let _a = connect(&board, "A", (100.0, 100.0), true);
+        let id = board.add((150.0, 100.0), (300.0, 200.0), &operator()).unwrap();
+        board.allocate();
+        assert_eq!(job(&board, id).robot.as_deref(), Some("A"));
+
+        board.control.robots().lock().unwrap().remove("A");
+        let _b = connect(&board, "B", (500.0, 300.0), true);
+        board.allocate();
+        let job = job(&board, id);
+        assert_eq!(job.reassigned, 1);
+        assert_eq!(job.robot.as_deref(), Some("B"));
+        assert_eq!(job.status, JobStatus::ToPickup);
+    }
+
+    #[test]
+    fn a_load_already_picked_up_is_collected_where_it_was_left() {
+        let board = board();
+        let _a = connect(&board, "A", (100.0, 100.0), true);
+        let id = board.add((150.0, 100.0), (300.0, 200.0), &operator()).unwrap();
+        board.allocate();
+        let pickup_at = job(&board, id).pickup_at;
+        report(&board, "A", MissionStatus::InProgress, pickup_at);
+        {
+            let mut robots = board.control.robots().lock().unwrap();
+            let state = &mut robots.get_mut("A").unwrap().state;
+            (state.x, state.y) = (200.0, 150.0);
+        }
+        board.allocate();
+
+        // Nobody else to hand the job to
+        board.control.robots().lock().unwrap().remove("A");
+        board.allocate();
+        let job = job(&board, id);
+        assert_eq!(job.status, JobStatus::Queued);
+        assert_eq!(job.pickup, (200.0, 150.0));
+        assert_eq!(job.dropoff, (300.0, 200.0));
+    }
+
+    #[test]
+    fn a_robot_stopped_too_long_loses_its_job() {
+        let board = board();
+        let a = connect(&board, "A", (100.0, 100.0), true);
+        let id = board.add((150.0, 100.0), (300.0, 200.0), &operator()).unwrap();
+        board.allocate();
+        let mission = job(&board, id).mission.unwrap();
+        while a.try_recv().is_ok() {}
+
+        board.control.robots().lock().unwrap().get_mut("A").unwrap().state.active = false;
+        board.allocate();
+        assert_eq!(job(&board, id).status, JobStatus::ToPickup);
+
+        // Pretend the robot has been stopped for longer than allowed
+        for job in board.queue.lock().unwrap().jobs.iter_mut() {
+            job.stopped_since = Some(Instant::now() - STOPPED_TOO_LONG - Duration::from_secs(1));
+        }
+        board.allocate();
+        let job = job(&board, id);
+        assert_eq!(job.status, JobStatus::Queued);
+        assert_eq!(job.reassigned, 1);
+        assert!(matches!(a.try_recv(), Ok(ServerMessage::CancelMission(m)) if m == mission));
+    }
+
+    #[test]
+    fn the_allocator_locks_the_jobs_before_the_robots() {
+        let board = board();
+        let _robot = connect(&board, "A", (100.0, 100.0), true);
+        board.add((150.0, 100.0), (300.0, 200.0), &operator()).unwrap();
+
+        let robots = board.control.robots().clone();
+        let held = robots.lock().unwrap();
+        let allocator = {
+            let board = board.clone();
+            thread::spawn(move || board.allocate())
+        };
+        // The allocator takes the jobs and then waits for the robots
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while board.queue.try_lock().is_ok() {
+            assert!(Instant::now() < deadline, "the allocator never locked the jobs");
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(held);
+        allocator.join().unwrap();
+        assert_eq!(board.list()[0].robot.as_deref(), Some("A"));
+    }
+}
@@ -0,0 +1,129 @@
+// Per-robot telemetry link quality: sequence gaps, clock offset and latency.
+//
+// Clock offset comes from Ping/Pong round trips (the NTP formula), keeping
+// the sample with the shortest round trip out of the last few since it has
+// the least queueing error. Telemetry send times are then mapped onto the
+// server clock to get one-way latency and the age of each position.
+use assignment2::{now_ms, Pong};
+use serde::Serialize;
+use std::collections::VecDeque;
+
+const CLOCK_SAMPLES: usize = 8;
+const LATENCY_SMOOTHING: f32 = 0.2;
+
+#[derive(Clone, Copy, Debug)]
+struct ClockSample {
+    rtt_ms: i64,
+    offset_ms: i64,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct LinkStats {
+    pub received: u64,
+    // Sequence numbers skipped over (never arrived, or arrived too late)
+    pub dropped: u64,
+    // Frames discarded for arriving after a newer one
+    pub reordered: u64,
+    // Robot clock minus server clock
+    pub clock_offset_ms: Option<i64>,
+    pub rtt_ms: Option<i64>,
+    // Smoothed one-way delay of telemetry frames
+    pub latency_ms: Option<f32>,
+    #[serde(skip)]
+    last_seq: Option<u64>,
+    #[serde(skip)]
+    samples: VecDeque<ClockSample>,
+}
+
+impl LinkStats {
+    // Records a telemetry frame and returns false if it is older than one
+    // already accepted. Unsequenced frames (seq 0) are always accepted.
+    pub fn accept(&mut self, seq: u64, sent_at_ms: u64) -> bool {
+        match self.last_seq {
+            Some(last) if seq != 0 && seq <= last => {
+                self.reordered += 1;
+                return false;
+            }
+            Some(last) if seq != 0 => self.dropped += seq - last - 1,
+            _ => {}
+        }
+        if seq != 0 {
+            self.last_seq = Some(seq);
+        }
+        self.received += 1;
+
+        if let Some(sent) = self.to_server_clock(sent_at_ms) {
+            let latency = (now_ms() as i64 - sent).max(0) as f32;
+            self.latency_ms = Some(match self.latency_ms {
+                Some(avg) => avg + LATENCY_SMOOTHING * (latency - avg),
+                None => latency,
+            });
+        }
+        true
+    }
+
+    // Folds in a Pong received at `now` (server clock)
+    pub fn clock_sample(&mut self, pong: &Pong, now: u64) {
+        let (t0, t1, t2, t3) = (pong.ping_ms as i64, pong.received_ms as i64, pong.sent_ms as i64, now as i64);
+        let sample = ClockSample { rtt_ms: ((t3 - t0) - (t2 - t1)).max(0), offset_ms: ((t1 - t0) + (t2 - t3)) / 2 };
+        self.samples.push_back(sample);
+        if self.samples.len() > CLOCK_SAMPLES {
+            self.samples.pop_front();
+        }
+        if let Some(best) = self.samples.iter().min_by_key(|s| s.rtt_ms) {
+            self.rtt_ms = Some(best.rtt_ms);
+            self.clock_offset_ms = Some(best.offset_ms);
+        }
+    }
+
+    // A robot timestamp expressed on the server clock, once the offset is known
+    pub fn to_server_clock(&self, robot_ms: u64) -> Option<i64> {
+        let offset = self.clock_offset_ms?;
+        (robot_ms != 0).then(|| robot_ms as i64 - offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_order_frames_are_accepted() {
+        let mut link = LinkStats::default();
+        assert!((1..=5).all(|seq| link.accept(seq, 0)));
+        assert_eq!((link.received, link.dropped, link.reordered), (5, 0, 0));
+    }
+
+    #[test]
+    fn duplicates_are_rejected() {
+        let mut link = LinkStats::default();
+        assert!(link.accept(1, 0));
+        assert!(link.accept(2, 0));
+        assert!(!link.accept(2, 0));
+        assert_eq!((link.received, link.reordered), (2, 1));
+    }
+
+    #[test]
+    fn late_frames_are_rejected_and_gaps_counted_once() {
+        let mut link = LinkStats::default();
+        assert!(link.accept(1, 0));
+        // 2 and 3 overtaken by 4: counted as dropped, then refused when they turn up
+        assert!(link.accept(4, 0));
+        assert!(!link.accept(3, 0));
+        assert!(!link.accept(2, 0));
+        assert!(link.accept(5, 0));
+        assert_eq!((link.received, link.dropped, link.reordered), (3, 2, 2));
+    }
+
+    #[test]
+    fn unsequenced_frames_are_always_accepted() {
+        let mut link = LinkStats::default();
+        assert!(link.accept(7, 0));
+        assert!(link.accept(0, 0));
+        assert!(link.accept(0, 0));
+        // They do not move the sequence on
+        assert!(!link.accept(7, 0));
+        assert!(link.accept(8, 0));
+        assert_eq!((link.received, link.dropped, link.reordered), (4, 0, 1));
+    }
+}
@@ -0,0 +1,913 @@
+// Server.rs - Collaborative Robots Central Controller
+mod api;
+mod auth;
+mod connection;
+mod control;
+mod delivery;
+mod jobs;
+mod link;
+mod map;
+mod mission;
+mod neighbours;
+mod operators;
+mod planner;
+mod safety;
+mod stream;
+mod traffic;
+mod udp;
+
+use assignment2::cli::Args;
+use assignment2::codec::DEFAULT_MAX_FRAME;
+use assignment2::footprint::{self, Footprint, Placed};
+use assignment2::transport::{self, NetStream};
+use assignment2::{now_ms, RobotState, ServerMessage, BOUNDARY_HEIGHT, BOUNDARY_WIDTH};
+use auth::Credentials;
+use connection::ClientContext;
+use control::{Command, CommandError, FleetControl, Target};
+use jobs::{JobBoard, JobStatus};
+use map::WorkspaceMap;
+use operators::{Operator, Operators, Role};
+use safety::SharedSafety;
+use traffic::SharedTraffic;
+use eframe::egui::{self, Color32, Pos2, Rect, CornerRadius, Stroke, Vec2, StrokeKind};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::TcpListener;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const BIND_ADDR: &str = "127.0.0.1:5050";
+const UDP_ADDR: &str = "127.0.0.1:5051";
+const API_ADDR: &str = "127.0.0.1:8080";
+const STREAM_HZ: f32 = 10.0; // Default WebSocket snapshot rate
+// Dead reckoning: between reports a moving robot is assumed to keep its speed
+// and heading, for at most MAX_EXTRAPOLATION past the measured position
+const MAX_EXTRAPOLATION: Duration = Duration::from_millis(500);
+// Positions extrapolated further than this are shown as predicted
+const PREDICTED_AFTER: Duration = Duration::from_millis(100);
+
+// What the next workspace click sets while a job is being placed on the map
+#[derive(PartialEq)]
+enum Placing {
+    Nothing,
+    Pickup,
+    Dropoff,
+}
+
+// Internal state for a single connected robot
+struct RobotData {
+    state: RobotState,
+    // Declared in the Hello
+    footprint: Footprint,
+    // Right of way when too close to another robot: the higher level keeps going.
+    // Declared in the Hello unless --priorities or an operator sets it.
+    priority: u8,
+    // Asked in the Hello to be sent the robots near it
+    neighbours: bool,
+    trail: VecDeque<Pos2>,
+    last_seen: std::time::Instant,
+    // Channel to send commands TO the specific client's writer thread
+    tx_to_client: mpsc::Sender<ServerMessage>,
+    // Dropped frames, clock offset and latency
+    link: link::LinkStats,
+    // Safety-critical commands waiting for the robot's Ack
+    outbox: delivery::SharedOutbox,
+    // Latest mission dispatched to the robot, kept after it ends
+    mission: Option<mission::Mission>,
+}
+
+impl RobotData {
+    // Applies a telemetry frame unless a newer one was already applied
+    fn update(&mut self, mut state: RobotState) {
+        if !self.link.accept(state.seq, state.sent_at_ms) {
+            return;
+        }
+
+        // The stopping distance is the robot's own claim; the safety and traffic
+        // checks walk along it, so keep it finite and within the workspace
+        let max_stop = BOUNDARY_WIDTH.hypot(BOUNDARY_HEIGHT);
+        state.stopping_distance =
+            if state.stopping_distance.is_finite() { state.stopping_distance.clamp(0.0, max_stop) } else { max_stop };
+
+        // Update Trail (Keep last 20)
+        self.trail.push_back(Pos2::new(state.x, state.y));
+        if self.trail.len() > 20 {
+            self.trail.pop_front();
+        }
+
+        // Update State
+        self.state = state;
+        self.last_seen = std::time::Instant::now();
+    }
+
+    // Time since the current position was measured on the robot. Falls back
+    // to the receive time until the robot's clock offset is known.
+    fn data_age(&self) -> Duration {
+        let received = self.last_seen.elapsed();
+        let measured = self
+            .link
+            .to_server_clock(self.state.sent_at_ms)
+            .map(|sent| Duration::from_millis((now_ms() as i64 - sent).max(0) as u64));
+        measured.map_or(received, |m| m.max(received))
+    }
+
+    // Best estimate of where the robot is now, and whether that estimate is
+    // mostly extrapolated rather than reported
+    fn estimated_position(&self) -> (Pos2, bool) {
+        let state = &self.state;
+        if state.speed == 0.0 {
+            return (Pos2::new(state.x, state.y), false);
+        }
+        let age = self.data_age().min(MAX_EXTRAPOLATION);
+        let mut travel = state.speed * age.as_secs_f32();
+        // A stopped robot still moving is braking and comes to rest within its stopping distance
+        if !state.active {
+            travel = travel.min(state.stopping_distance);
+        }
+        // Same clamp as the client's own physics
+        let x = (state.x + travel * state.angle.cos()).clamp(0.0, BOUNDARY_WIDTH);
+        let y = (state.y + travel * state.angle.sin()).clamp(0.0, BOUNDARY_HEIGHT);
+        (Pos2::new(x, y), age > PREDICTED_AFTER)
+    }
+
+    // Footprint at the estimated position, turned to the body heading
+    fn outline(&self) -> Placed {
+        let pos = self.estimated_position().0;
+        self.footprint.place(pos.x, pos.y, self.state.body_heading())
+    }
+}
+
+// Shared state accessed by GUI and Networking threads
+type SharedRobots = Arc<Mutex<HashMap<String, RobotData>>>;
+
+// Command-line configuration
+struct ServerConfig {
+    api_addr: String,
+    operators: Operators,
+    stream_hz: f32,
+    credentials: Credentials,
+    // Set when --tls-cert/--tls-key are given; plaintext otherwise
+    tls: Option<Arc<rustls::ServerConfig>>,
+    // Largest frame accepted from a robot; bigger ones close the connection
+    max_frame: usize,
+    udp_addr: String,
+    // Obstacles and keep-out zones for path planning; empty without --map
+    map: WorkspaceMap,
+    // Right-of-way levels by robot ID, overriding those declared in the Hello
+    priorities: HashMap<String, u8>,
+    // Footprint gap within which robots that ask are sent their neighbours; 0 = never
+    neighbour_radius: f32,
+}
+
+impl ServerConfig {
+    fn from_args(args: &Args) -> Self {
+        Self {
+            api_addr: args.value("--api-addr").unwrap_or(API_ADDR).to_string(),
+            operators: {
+                let api_token = args
+                    .value("--api-token")
+                    .map(String::from)
+                    .or_else(|| std::env::var("COBOT_API_TOKEN").ok());
+                match args.value("--operators") {
+                    Some(path) => {
+                        if api_token.is_some() {
+                            eprintln!("Ignoring --api-token: API tokens come from {}", path);
+                        }
+                        Operators::load(path).unwrap_or_else(|e| {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        })
+                    }
+                    None => Operators::unrestricted(api_token),
+                }
+            },
+            stream_hz: match args.parse_or("--stream-hz", STREAM_HZ) {
+                hz if hz.is_finite() => hz,
+                hz => {
+                    eprintln!("Ignoring invalid value for --stream-hz: {}", hz);
+                    STREAM_HZ
+                }
+            },
+            max_frame: args.parse_or("--max-frame", DEFAULT_MAX_FRAME),
+            udp_addr: args.value("--udp-addr").unwrap_or(UDP_ADDR).to_string(),
+            neighbour_radius: match args.parse_or("--neighbour-radius", neighbours::NEIGHBOUR_RADIUS) {
+                radius if radius.is_finite() && radius >= 0.0 => radius,
+                radius => {
+                    eprintln!("Ignoring invalid value for --neighbour-radius: {}", radius);
+                    neighbours::NEIGHBOUR_RADIUS
+                }
+            },
+            map: match args.value("--map") {
+                Some(path) => WorkspaceMap::load(path).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }),
+                None => WorkspaceMap::default(),
+            },
+            priorities: match args.value("--priorities") {
+                Some(path) => load_priorities(path).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }),
+                None => HashMap::new(),
+            },
+            credentials: match args.value("--credentials") {
+                Some(path) => Credentials::load(path).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }),
+                None => Credentials::disabled(),
+            },
+            tls: match (args.value("--tls-cert"), args.value("--tls-key")) {
+                (Some(cert), Some(key)) => {
+                    let config = transport::server_config(cert, key, args.value("--tls-client-ca"));
+                    Some(config.unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }))
+                }
+                (None, None) => None,
+                _ => {
+                    eprintln!("--tls-cert and --tls-key must be given together");
+                    std::process::exit(1);
+                }
+            },
+        }
+    }
+}
+
+// Fills an outline that may be concave, and strokes its edge
+fn paint_outline(
+    painter: &egui::Painter,
+    outline: &[(f32, f32)],
+    to_screen: impl Fn(Pos2) -> Pos2,
+    fill: Color32,
+    stroke: Stroke,
+) {
+    let points: Vec<Pos2> = outline.iter().map(|&(x, y)| to_screen(Pos2::new(x, y))).collect();
+    let mut mesh = egui::Mesh::default();
+    for &p in &points {
+        mesh.colored_vertex(p, fill);
+    }
+    for [a, b, c] in footprint::triangulate(outline) {
+        mesh.add_triangle(a as u32, b as u32, c as u32);
+    }
+    painter.add(egui::Shape::mesh(mesh));
+    if stroke != Stroke::NONE {
+        painter.add(egui::Shape::closed_line(points, stroke));
+    }
+}
+
+// Priorities file: a JSON object mapping robot ID to level, e.g. { "Cart": 10, "Rover": 0 }
+fn load_priorities(path: &str) -> Result<HashMap<String, u8>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Invalid priorities file {} (levels are 0-255): {}", path, e))
+}
+
+fn main() -> eframe::Result<()> {
+    let args = Args::from_env();
+    // Prints the stored form of a password read from stdin, for the operators file
+    if args.flag("--hash-password") {
+        let mut password = String::new();
+        if let Err(e) = std::io::stdin().read_line(&mut password) {
+            eprintln!("Cannot read password: {}", e);
+            std::process::exit(1);
+        }
+        println!("{}", operators::hash_password(password.trim_end_matches(['\r', '\n'])));
+        return Ok(());
+    }
+    let config = ServerConfig::from_args(&args);
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([800.0, 600.0]),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "Cobot Central Server",
+        options,
+        Box::new(|_cc| Ok(Box::new(ServerApp::new(config)))),
+    )
+}
+
+struct ServerApp {
+    robots: SharedRobots,
+    log: Vec<String>,
+    rx_log: mpsc::Receiver<String>,
+    safety: SharedSafety,
+    traffic: SharedTraffic,
+    control: FleetControl,
+    global_speed_limit: f32,
+
+    // Operator login
+    operators: Arc<Operators>,
+    operator: Option<Operator>,
+    login_name: String,
+    login_password: String,
+    // Password check running off the UI thread (PBKDF2 takes a noticeable moment)
+    pending_login: Option<mpsc::Receiver<Result<Operator, String>>>,
+
+    // Mission dispatch: the robot picked in the workspace and a path being built for it
+    selected: Option<String>,
+    draft_path: Vec<(f32, f32)>,
+    draft_looping: bool,
+    // Plan clicks and paths around the map instead of driving straight
+    plan_paths: bool,
+
+    // Transport jobs, and the one being entered
+    jobs: JobBoard,
+    job_pickup: (f32, f32),
+    job_dropoff: (f32, f32),
+    placing: Placing,
+}
+
+impl ServerApp {
+    fn new(config: ServerConfig) -> Self {
+        let (tx_log, rx_log) = mpsc::channel();
+        let robots = Arc::new(Mutex::new(HashMap::new()));
+
+        let tx_log_clone = tx_log.clone();
+        let credentials = Arc::new(config.credentials);
+        let tls = config.tls;
+        let client_ctx = ClientContext {
+            max_frame: config.max_frame,
+            robots: robots.clone(),
+            udp: udp::UdpHub::spawn(&config.udp_addr, robots.clone(), config.max_frame, tx_log.clone()),
+            credentials: credentials.clone(),
+            failed: Arc::new(auth::FailedAttempts::default()),
+            priorities: Arc::new(config.priorities),
+            tx_log: tx_log.clone(),
+            connected: Arc::new(Mutex::new(HashSet::new())),
+        };
+
+        // Spawn Listener Thread
+        thread::spawn(move || {
+            let listener = TcpListener::bind(BIND_ADDR).expect("Failed to bind");
+            let _ = tx_log_clone.send(format!(
+                "Server listening on {} ({})",
+                BIND_ADDR,
+                if tls.is_some() { "TLS" } else { "plaintext" }
+            ));
+            if !credentials.enabled() {
+                let _ = tx_log_clone.send("WARNING: robot authentication disabled (no --credentials)".into());
+            }
+
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let ctx = client_ctx.clone();
+                        let tls_ref = tls.clone();
+                        // Spawn a handler per client
+                        thread::spawn(move || {
+                            let stream = match tls_ref {
+                                Some(config) => {
+                                    let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                                    match NetStream::accept_tls(stream, config) {
+                                        Ok(s) => s,
+                                        Err(e) => {
+                                            let _ = ctx.tx_log.send(format!("TLS handshake failed from {}: {}", peer, e));
+                                            return;
+                                        }
+                                    }
+                                }
+                                None => NetStream::plain(stream),
+                            };
+                            connection::handle_client(stream, ctx)
+                        });
+                    }
+                    Err(e) => {
+                        let _ = tx_log_clone.send(format!("Connection failed: {}", e));
+                    }
+                }
+            }
+        });
+
+        let safety = safety::spawn_monitor(robots.clone(), tx_log.clone());
+        neighbours::spawn(robots.clone(), config.neighbour_radius);
+        let zones = config.map.obstacles.len() + config.map.keep_out.len();
+        if zones > 0 {
+            let _ = tx_log.send(format!("Map loaded: {} obstacle(s), {} keep-out zone(s)", config.map.obstacles.len(), config.map.keep_out.len()));
+        }
+        let control = FleetControl::new(robots.clone(), tx_log.clone(), config.map);
+        let traffic = traffic::spawn(control.clone(), tx_log.clone());
+        let jobs = JobBoard::new(control.clone(), tx_log.clone());
+        jobs.spawn_allocator();
+        let operators = Arc::new(config.operators);
+        let api_ctx = api::ApiContext {
+            operators: operators.clone(),
+            control: control.clone(),
+            jobs: jobs.clone(),
+            safety: safety.clone(),
+            traffic: traffic.clone(),
+            stream_hz: config.stream_hz,
+        };
+        api::spawn(config.api_addr, api_ctx, tx_log);
+
+        Self {
+            robots,
+            log: vec![],
+            rx_log,
+            safety,
+            traffic,
+            global_speed_limit: control.global_speed_limit(),
+            control,
+            operator: operators.console_default(),
+            operators,
+            login_name: String::new(),
+            login_password: String::new(),
+            pending_login: None,
+            selected: None,
+            draft_path: Vec::new(),
+            draft_looping: false,
+            plan_paths: true,
+            jobs,
+            job_pickup: (100.0, 100.0),
+            job_dropoff: (BOUNDARY_WIDTH - 100.0, BOUNDARY_HEIGHT - 100.0),
+            placing: Placing::Nothing,
+        }
+    }
+
+    fn login_ui(&mut self, ui: &mut egui::Ui) {
+        match &self.operator {
+            Some(op) => {
+                ui.label(format!("Operator: {} ({})", op.name, op.role));
+                if self.operators.console_default().is_none() && ui.button("Log out").clicked() {
+                    self.log.push(format!("Operator {} logged out", op.name));
+                    self.operator = None;
+                }
+            }
+            None => {
+                if let Some(rx) = &self.pending_login {
+                    match rx.try_recv() {
+                        Ok(Ok(op)) => {
+                            self.log.push(format!("Operator {} logged in as {}", op.name, op.role));
+                            self.operator = Some(op);
+                            self.pending_login = None;
+                            return;
+                        }
+                        Ok(Err(e)) => {
+                            self.log.push(format!("LOGIN FAILED for '{}': {}", self.login_name, e));
+                            self.pending_login = None;
+                        }
+                        Err(mpsc::TryRecvError::Empty) => {
+                            ui.label(format!("Logging in as {}...", self.login_name));
+                            return;
+                        }
+                        Err(mpsc::TryRecvError::Disconnected) => self.pending_login = None,
+                    }
+                }
+                ui.label("Operator login:");
+                ui.text_edit_singleline(&mut self.login_name);
+                ui.add(egui::TextEdit::singleline(&mut self.login_password).password(true));
+                if ui.button("Log in").clicked() {
+                    let (tx, rx) = mpsc::channel();
+                    let operators = self.operators.clone();
+                    let name = self.login_name.clone();
+                    let password = std::mem::take(&mut self.login_password);
+                    thread::spawn(move || {
+                        let _ = tx.send(operators.login(&name, &password));
+                    });
+                    self.pending_login = Some(rx);
+                }
+            }
+        }
+    }
+
+    // Button that is only enabled when the logged-in operator may issue `cmd`
+    fn command_button(&self, ui: &mut egui::Ui, label: &str, cmd: Command) {
+        let allowed = self.operator.as_ref().is_some_and(|op| self.control.allowed(&cmd, op));
+        if ui.add_enabled(allowed, egui::Button::new(label)).clicked()
+            && let Some(op) = &self.operator
+        {
+            let _ = self.control.execute(&Target::All, cmd, op);
+        }
+    }
+
+    // Sends a GoTo / FollowPath to the selected robot as the logged-in operator
+    fn dispatch(&mut self, cmd: Command) {
+        let (Some(op), Some(id)) = (&self.operator, &self.selected) else {
+            self.log.push("Log in to dispatch missions".into());
+            return;
+        };
+        match self.control.execute(&Target::Robot(id.clone()), cmd, op) {
+            // Refusals are logged by the control layer
+            Ok(_) | Err(CommandError::Forbidden(_)) => {}
+            Err(e) => self.log.push(format!("Dispatch to {} failed: {}", id, e)),
+        }
+    }
+
+    fn dispatch_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Mission Dispatch:");
+        match &self.selected {
+            Some(id) => ui.label(format!("{} selected. Click the workspace to send it there, shift-click to plan a path.", id)),
+            None => ui.label("Click a robot in the workspace to select it."),
+        };
+        let selected = self.selected.clone();
+        let priority = selected.as_ref().and_then(|id| Some(self.robots.lock().unwrap().get(id)?.priority));
+        if let (Some(id), Some(mut priority)) = (selected, priority) {
+            let allowed = self.operator.as_ref().is_some_and(|op| self.control.allowed(&Command::SetPriority(priority), op));
+            ui.horizontal(|ui| {
+                ui.label("Priority:");
+                let edit = egui::DragValue::new(&mut priority);
+                if ui.add_enabled(allowed, edit).on_hover_text("Higher keeps going when robots get too close").changed()
+                    && let Some(op) = &self.operator
+                {
+                    let _ = self.control.execute(&Target::Robot(id), Command::SetPriority(priority), op);
+                }
+            });
+        }
+        ui.checkbox(&mut self.plan_paths, "Plan around obstacles")
+            .on_hover_text("Plans a path from the robot through each point clicked. Looping paths are sent as drawn.");
+        if !self.draft_path.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label(format!("Path: {} waypoint(s)", self.draft_path.len()));
+                ui.checkbox(&mut self.draft_looping, "Loop");
+            });
+            ui.horizontal(|ui| {
+                if ui.add_enabled(self.selected.is_some(), egui::Button::new("Send Path")).clicked() {
+                    let waypoints = std::mem::take(&mut self.draft_path);
+                    if self.plan_paths && !self.draft_looping {
+                        self.dispatch(Command::Navigate { goals: waypoints });
+                    } else {
+                        self.dispatch(Command::FollowPath { waypoints, looping: self.draft_looping });
+                    }
+                }
+                if ui.button("Clear Path").clicked() {
+                    self.draft_path.clear();
+                }
+            });
+        }
+
+        let guard = self.robots.lock().unwrap();
+        let mut missions: Vec<_> = guard.values().filter_map(|r| Some((&r.state.id, r.mission.as_ref()?))).collect();
+        missions.sort_by(|a, b| a.0.cmp(b.0));
+        for (id, mission) in missions {
+            ui.small(format!("{}: #{} {} ({})", id, mission.id, mission.describe(), mission.status_text()));
+        }
+        for (id, waits) in &self.traffic.lock().unwrap().held {
+            ui.colored_label(Color32::ORANGE, format!("{} held by traffic for {}", id, waits.join(", ")));
+        }
+    }
+
+    fn jobs_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Jobs");
+        let allowed = self.operator.as_ref().is_some_and(|op| op.role >= Role::Operator);
+        for (label, point) in [("Pickup:", &mut self.job_pickup), ("Drop-off:", &mut self.job_dropoff)] {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                ui.add(egui::DragValue::new(&mut point.0).range(0.0..=BOUNDARY_WIDTH).prefix("x "));
+                ui.add(egui::DragValue::new(&mut point.1).range(0.0..=BOUNDARY_HEIGHT).prefix("y "));
+            });
+        }
+        ui.horizontal(|ui| {
+            if ui.add_enabled(allowed, egui::Button::new("Add Job")).clicked() {
+                self.add_job();
+            }
+            let placing = self.placing != Placing::Nothing;
+            let label = if placing { "Stop Placing" } else { "Place on Map" };
+            if ui.add_enabled(allowed, egui::Button::new(label)).clicked() {
+                self.placing = if placing { Placing::Nothing } else { Placing::Pickup };
+            }
+        });
+        match self.placing {
+            Placing::Pickup => ui.label("Click the pickup point in the workspace."),
+            Placing::Dropoff => ui.label("Click the drop-off point in the workspace."),
+            Placing::Nothing => ui.small(format!("Jobs go to the nearest idle robot, and to another one after {} s stopped.", jobs::STOPPED_TOO_LONG.as_secs())),
+        };
+
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for job in self.jobs.list() {
+                ui.horizontal(|ui| {
+                    let color = match job.status {
+                        JobStatus::Queued => Color32::LIGHT_GRAY,
+                        JobStatus::ToPickup | JobStatus::ToDropoff => Color32::ORANGE,
+                        JobStatus::Done => Color32::GREEN,
+                        JobStatus::Cancelled => Color32::DARK_GRAY,
+                    };
+                    let robot = job.robot.as_ref().map_or(String::new(), |r| format!(" ({})", r));
+                    ui.colored_label(color, format!("#{} {}: {}{}", job.id, job.describe(), job.status, robot))
+                        .on_hover_text(format!("Queued by {}, reassigned {} time(s)", job.created_by, job.reassigned));
+                    if job.is_open()
+                        && ui.add_enabled(allowed, egui::Button::new("Cancel").small()).clicked()
+                        && let Some(op) = &self.operator
+                        && let Err(e) = self.jobs.cancel(job.id, op)
+                    {
+                        self.log.push(format!("Job #{} not cancelled: {}", job.id, e));
+                    }
+                });
+            }
+        });
+    }
+
+    fn add_job(&mut self) {
+        let Some(op) = &self.operator else {
+            self.log.push("Log in to add jobs".into());
+            return;
+        };
+        if let Err(e) = self.jobs.add(self.job_pickup, self.job_dropoff, op) {
+            self.log.push(format!("Job not added: {}", e));
+        }
+    }
+}
+
+impl eframe::App for ServerApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // 1. Process Logs
+        while let Ok(msg) = self.rx_log.try_recv() {
+            self.log.push(msg);
+            if self.log.len() > 50 { self.log.remove(0); }
+        }
+
+        // Pick up limits set remotely through the control API
+        self.global_speed_limit = self.control.global_speed_limit();
+
+        egui::SidePanel::left("controls").show(ctx, |ui| {
+            ui.heading("Server Controls");
+            ui.separator();
+            ui.label(format!("Connected Bots: {}", self.robots.lock().unwrap().len()));
+            {
+                let guard = self.robots.lock().unwrap();
+                let mut robots: Vec<_> = guard.values().collect();
+                robots.sort_by(|a, b| a.state.id.cmp(&b.state.id));
+                for robot in robots {
+                    let link = &robot.link;
+                    let latency = link.latency_ms.map_or("? ms".to_string(), |l| format!("{:.0} ms", l));
+                    let text = format!(
+                        "{} (P{}): {} latency, {} dropped, {} reordered",
+                        robot.state.id, robot.priority, latency, link.dropped, link.reordered
+                    );
+                    let label = if robot.data_age() > safety::STALE_AFTER {
+                        ui.colored_label(Color32::RED, format!("{} (STALE)", text))
+                    } else {
+                        ui.small(text)
+                    };
+                    label.on_hover_text(format!("Drive: {}", robot.state.model.describe()));
+                }
+            }
+
+            ui.separator();
+            self.login_ui(ui);
+
+            ui.separator();
+            ui.label("Global Safety Override:");
+            self.command_button(ui, "EMERGENCY STOP ALL", Command::Stop);
+            self.command_button(ui, "Resume All", Command::Resume);
+            if self.control.estop_latched() {
+                ui.colored_label(Color32::RED, "E-STOP LATCHED (supervisor resume required)");
+            }
+            {
+                let guard = self.robots.lock().unwrap();
+                let mut overdue: Vec<_> =
+                    guard.values().filter_map(|r| Some((&r.state.id, r.outbox.lock().unwrap().overdue_stop()?))).collect();
+                overdue.sort_by(|a, b| a.0.cmp(b.0));
+                for (id, age) in overdue {
+                    ui.colored_label(Color32::RED, format!("ALARM: {} has not acknowledged STOP ({:.1} s)", id, age.as_secs_f32()));
+                }
+            }
+
+            ui.separator();
+            ui.label("Global Speed Limit (Novel Feature):");
+            let cmd = Command::SetSpeedLimit(self.global_speed_limit);
+            let allowed = self.operator.as_ref().is_some_and(|op| self.control.allowed(&cmd, op));
+            let slider = egui::Slider::new(&mut self.global_speed_limit, 0.0..=200.0).text("Max Speed");
+            if ui.add_enabled(allowed, slider).changed()
+                && let Some(op) = &self.operator
+            {
+                let _ = self.control.execute(&Target::All, Command::SetSpeedLimit(self.global_speed_limit), op);
+            }
+
+            ui.separator();
+            self.dispatch_ui(ui);
+
+            ui.separator();
+            ui.heading("Log");
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for line in &self.log {
+                    ui.monospace(line);
+                }
+            });
+        });
+
+        egui::SidePanel::right("jobs").show(ctx, |ui| self.jobs_ui(ui));
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Workspace Visualization");
+            
+            // Allocate a painting region
+            let (response, painter) = ui.allocate_painter(
+                Vec2::new(BOUNDARY_WIDTH + 50.0, BOUNDARY_HEIGHT + 50.0), 
+                egui::Sense::click()
+            );
+
+            // Click a robot to select it, then click elsewhere to send it there
+            // (shift-click adds to a path instead)
+            if response.clicked()
+                && let Some(click) = response.interact_pointer_pos()
+            {
+                let point = click - response.rect.min;
+                let hit = self.robots.lock().unwrap().values().find_map(|r| {
+                    let centre = r.estimated_position().0.to_vec2();
+                    ((centre - point).length() <= r.footprint.reach() + 3.0).then(|| r.state.id.clone())
+                });
+                let inside = point.x <= BOUNDARY_WIDTH && point.y <= BOUNDARY_HEIGHT;
+                match hit {
+                    // Placing a job takes the click first
+                    _ if self.placing == Placing::Pickup && inside => {
+                        self.job_pickup = (point.x, point.y);
+                        self.placing = Placing::Dropoff;
+                    }
+                    _ if self.placing == Placing::Dropoff && inside => {
+                        self.job_dropoff = (point.x, point.y);
+                        self.placing = Placing::Nothing;
+                        self.add_job();
+                    }
+                    Some(id) => {
+                        self.selected = (self.selected.as_ref() != Some(&id)).then_some(id);
+                        self.draft_path.clear();
+                    }
+                    None if self.selected.is_some() && inside => {
+                        if ui.input(|i| i.modifiers.shift) {
+                            self.draft_path.push((point.x, point.y));
+                        } else if self.plan_paths {
+                            self.dispatch(Command::Navigate { goals: vec![(point.x, point.y)] });
+                        } else {
+                            self.dispatch(Command::GoTo { x: point.x, y: point.y });
+                        }
+                    }
+                    None => {}
+                }
+            }
+
+            // Draw Boundary
+            let to_screen = |pos: Pos2| -> Pos2 {
+                response.rect.min + Vec2::new(pos.x, pos.y)
+            };
+
+            let boundary_rect = Rect::from_min_size(
+                to_screen(Pos2::new(0.0, 0.0)), 
+                Vec2::new(BOUNDARY_WIDTH, BOUNDARY_HEIGHT)
+            );
+            
+            painter.rect_stroke(boundary_rect, CornerRadius::ZERO, Stroke::new(2.0, Color32::GRAY), StrokeKind::Middle);
+
+            // Obstacles in grey, keep-out zones in red
+            let zones = self.control.map().obstacles.iter().map(|z| (z, Color32::from_gray(90), Color32::GRAY));
+            let keep_out = self.control.map().keep_out.iter().map(|z| (z, Color32::from_rgba_unmultiplied(255, 0, 0, 40), Color32::RED));
+            for (zone, fill, line) in zones.chain(keep_out) {
+                match zone.outline() {
+                    Placed::Circle { centre: (x, y), radius } => {
+                        painter.circle(to_screen(Pos2::new(x, y)), radius, fill, Stroke::new(1.0, line));
+                    }
+                    Placed::Polygon(points) => {
+                        paint_outline(&painter, &points, to_screen, fill, Stroke::new(1.0, line));
+                    }
+                }
+            }
+
+            // Open jobs: a square on the pickup (until the load is on board) and a ring on the drop-off
+            for job in self.jobs.list().iter().filter(|j| j.is_open()) {
+                let pickup = to_screen(Pos2::new(job.pickup.0, job.pickup.1));
+                let dropoff = to_screen(Pos2::new(job.dropoff.0, job.dropoff.1));
+                let color = if job.status == JobStatus::Queued { Color32::LIGHT_GRAY } else { Color32::ORANGE };
+                if job.status != JobStatus::ToDropoff {
+                    painter.line_segment([pickup, dropoff], Stroke::new(1.0, color.linear_multiply(0.4)));
+                    painter.rect_stroke(Rect::from_center_size(pickup, Vec2::splat(8.0)), CornerRadius::ZERO, Stroke::new(2.0, color), StrokeKind::Middle);
+                }
+                painter.circle_stroke(dropoff, 5.0, Stroke::new(2.0, color));
+                painter.text(dropoff + Vec2::new(7.0, -7.0), egui::Align2::LEFT_BOTTOM, format!("J{}", job.id), egui::FontId::proportional(11.0), color);
+            }
+            if self.placing == Placing::Dropoff {
+                let pickup = to_screen(Pos2::new(self.job_pickup.0, self.job_pickup.1));
+                painter.rect_stroke(Rect::from_center_size(pickup, Vec2::splat(8.0)), CornerRadius::ZERO, Stroke::new(2.0, Color32::YELLOW), StrokeKind::Middle);
+            }
+
+            // Cells reserved by the traffic manager, tinted with the robot holding them,
+            // and a line from each held robot to those it waits for
+            if let (Ok(guard), Ok(traffic)) = (self.robots.lock(), self.traffic.lock()) {
+                for (&(col, row), id) in &traffic.reserved {
+                    let Some(robot) = guard.get(id) else { continue };
+                    let corner = Pos2::new(col as f32 * traffic::CELL, row as f32 * traffic::CELL);
+                    let cell = Rect::from_min_size(to_screen(corner), Vec2::splat(traffic::CELL)).intersect(boundary_rect);
+                    if cell.is_positive() {
+                        let (r, g, b) = robot.state.color;
+                        painter.rect_filled(cell, CornerRadius::ZERO, Color32::from_rgba_unmultiplied(r, g, b, 30));
+                    }
+                }
+                for (id, waits) in &traffic.held {
+                    let Some(robot) = guard.get(id) else { continue };
+                    let pos = to_screen(robot.estimated_position().0);
+                    for other in waits.iter().filter_map(|w| guard.get(w)) {
+                        let to = to_screen(other.estimated_position().0);
+                        painter.add(egui::Shape::dashed_line(&[pos, to], Stroke::new(1.0, Color32::ORANGE), 4.0, 4.0));
+                    }
+                    painter.text(
+                        pos + Vec2::new(0.0, robot.footprint.reach() + 4.0),
+                        egui::Align2::CENTER_TOP,
+                        "HOLD",
+                        egui::FontId::monospace(10.0),
+                        Color32::ORANGE,
+                    );
+                }
+            }
+
+            // Proximity heatmap from the safety monitor
+            if let (Ok(guard), Ok(report)) = (self.robots.lock(), self.safety.lock()) {
+                for pair in &report.proximity {
+                    if let (Some(r1), Some(r2)) = (guard.get(&pair.a), guard.get(&pair.b)) {
+                        // Draw red connection line
+                        painter.line_segment(
+                            [to_screen(r1.estimated_position().0), to_screen(r2.estimated_position().0)],
+                            Stroke::new(1.0, Color32::RED.linear_multiply(0.5))
+                        );
+                    }
+                }
+            }
+
+            if let Ok(guard) = self.robots.lock() {
+                if self.selected.as_ref().is_some_and(|id| !guard.contains_key(id)) {
+                    self.selected = None;
+                    self.draft_path.clear();
+                }
+
+                // Draw Robots
+                for robot in guard.values() {
+                    let (estimate, predicted) = robot.estimated_position();
+                    let pos = to_screen(estimate);
+                    let color = Color32::from_rgb(robot.state.color.0, robot.state.color.1, robot.state.color.2);
+
+                    // Rest of the mission: the path ahead and a cross on its last waypoint
+                    if let Some(mission) = &robot.mission {
+                        let ahead = mission.remaining();
+                        let mut points = vec![pos];
+                        points.extend(ahead.iter().map(|&(x, y)| to_screen(Pos2::new(x, y))));
+                        painter.add(egui::Shape::dashed_line(&points, Stroke::new(1.0, color), 6.0, 4.0));
+                        if let Some(&(x, y)) = ahead.last().filter(|_| !mission.looping) {
+                            let goal = to_screen(Pos2::new(x, y));
+                            let stroke = Stroke::new(2.0, color);
+                            painter.line_segment([goal - Vec2::splat(5.0), goal + Vec2::splat(5.0)], stroke);
+                            painter.line_segment([goal + Vec2::new(-5.0, 5.0), goal + Vec2::new(5.0, -5.0)], stroke);
+                        }
+                    }
+
+                    // Selected for dispatch, with the path being planned for it
+                    if self.selected.as_ref() == Some(&robot.state.id) {
+                        painter.circle_stroke(pos, robot.footprint.reach() + 9.0, Stroke::new(2.0, Color32::YELLOW));
+                        let mut points = vec![pos];
+                        points.extend(self.draft_path.iter().map(|&(x, y)| to_screen(Pos2::new(x, y))));
+                        for &p in &points[1..] {
+                            painter.circle_filled(p, 3.0, Color32::YELLOW);
+                        }
+                        if self.draft_looping && points.len() > 2 {
+                            points.push(points[1]);
+                        }
+                        painter.add(egui::Shape::line(points, Stroke::new(1.0, Color32::YELLOW)));
+                    }
+                    
+                    // Draw Trail
+                    let points: Vec<Pos2> = robot.trail.iter().map(|p| to_screen(*p)).collect();
+                    painter.add(egui::Shape::line(points, Stroke::new(1.0, color.linear_multiply(0.5))));
+
+                    // Draw Robot Body (faded with an outline while only predicted)
+                    let (fill, stroke) = if predicted {
+                        (color.linear_multiply(0.4), Stroke::new(1.0, Color32::LIGHT_GRAY))
+                    } else {
+                        (color, Stroke::NONE)
+                    };
+                    match robot.outline() {
+                        Placed::Circle { radius, .. } => {
+                            painter.circle(pos, radius, fill, stroke);
+                        }
+                        Placed::Polygon(points) => {
+                            paint_outline(&painter, &points, to_screen, fill, stroke);
+                        }
+                    }
+                    // Tick showing which way the body faces
+                    let reach = robot.footprint.reach();
+                    let facing = Vec2::angled(robot.state.body_heading()) * (reach + 4.0);
+                    painter.line_segment([pos, pos + facing], Stroke::new(2.0, Color32::WHITE));
+                    painter.text(
+                        pos + Vec2::new(0.0, -(reach + 5.0)),
+                        egui::Align2::CENTER_BOTTOM,
+                        match (predicted, robot.priority) {
+                            (true, _) => format!("{} (predicted)", robot.state.id),
+                            (false, 0) => robot.state.id.clone(),
+                            (false, priority) => format!("{} P{}", robot.state.id, priority),
+                        },
+                        egui::FontId::proportional(12.0),
+                        Color32::WHITE,
+                    );
+                    
+                    if !robot.state.active {
+                        painter.text(pos, egui::Align2::CENTER_CENTER, "STOP", egui::FontId::monospace(10.0), Color32::RED);
+                    }
+                    if robot.outbox.lock().unwrap().overdue_stop().is_some() {
+                        painter.circle_stroke(pos, reach + 6.0, Stroke::new(3.0, Color32::RED));
+                    }
+                }
+            }
+        });
+
+        // Constant refresh for animation
+        ctx.request_repaint_after(Duration::from_millis(30));
+    }
+}
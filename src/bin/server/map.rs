@@ -0,0 +1,75 @@
+// Workspace map: static obstacles and keep-out zones, loaded with --map.
+//
+//   {
+//     "obstacles": [
+//       { "rect": { "x": 250, "y": 100, "width": 40, "height": 200 } },
+//       { "circle": { "x": 450, "y": 300, "radius": 30 } }
+//     ],
+//     "keep_out": [
+//       { "polygon": { "points": [[500, 20], [580, 20], [580, 90]] } }
+//     ]
+//   }
+//
+// Obstacles are physical, keep-out zones are floor robots must stay off (a
+// walkway, a loading bay). The planner avoids both. Polygons may be concave.
+use assignment2::footprint::Placed;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Zone {
+    // Top-left corner and size, in workspace coordinates
+    Rect { x: f32, y: f32, width: f32, height: f32 },
+    Circle { x: f32, y: f32, radius: f32 },
+    Polygon { points: Vec<(f32, f32)> },
+}
+
+impl Zone {
+    pub fn outline(&self) -> Placed {
+        match self {
+            Zone::Rect { x, y, width, height } => {
+                Placed::Polygon(vec![(*x, *y), (x + width, *y), (x + width, y + height), (*x, y + height)])
+            }
+            Zone::Circle { x, y, radius } => Placed::Circle { centre: (*x, *y), radius: *radius },
+            Zone::Polygon { points } => Placed::Polygon(points.clone()),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let ok = match self {
+            Zone::Rect { width, height, .. } => *width > 0.0 && *height > 0.0,
+            Zone::Circle { radius, .. } => *radius > 0.0,
+            Zone::Polygon { points } => points.len() >= 3,
+        };
+        if ok { Ok(()) } else { Err(format!("Map zone {:?} has no area", self)) }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WorkspaceMap {
+    #[serde(default)]
+    pub obstacles: Vec<Zone>,
+    #[serde(default)]
+    pub keep_out: Vec<Zone>,
+}
+
+impl WorkspaceMap {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let map: WorkspaceMap = serde_json::from_str(&text).map_err(|e| format!("Invalid map file {}: {}", path, e))?;
+        map.zones().try_for_each(|(zone, _)| zone.validate())?;
+        Ok(map)
+    }
+
+    // Every zone, with what it is ("an obstacle" or "a keep-out zone")
+    pub fn zones(&self) -> impl Iterator<Item = (&Zone, &'static str)> {
+        let obstacles = self.obstacles.iter().map(|z| (z, "an obstacle"));
+        obstacles.chain(self.keep_out.iter().map(|z| (z, "a keep-out zone")))
+    }
+
+    // What the point lies inside, if anything
+    pub fn zone_at(&self, (x, y): (f32, f32)) -> Option<&'static str> {
+        let point = Placed::Circle { centre: (x, y), radius: 0.0 };
+        self.zones().find(|(zone, _)| point.clearance(&zone.outline()) == 0.0).map(|(_, what)| what)
+    }
+}
@@ -0,0 +1,169 @@
+// Missions dispatched to robots with GoTo / FollowPath, and the progress
+// the robots report back.
+use assignment2::{MissionProgress, MissionStatus};
+use serde::Serialize;
+use std::time::Instant;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Mission {
+    pub id: u64,
+    pub waypoints: Vec<(f32, f32)>,
+    pub looping: bool,
+    // Operator who dispatched it
+    pub issued_by: String,
+    // None until the robot reports that it started
+    pub status: Option<MissionStatus>,
+    pub reached: usize,
+    // Sent by the traffic manager, still to be driven before the next waypoint
+    pub detour: Vec<(f32, f32)>,
+    #[serde(skip)]
+    pub issued: Instant,
+}
+
+impl Mission {
+    pub fn new(id: u64, waypoints: Vec<(f32, f32)>, looping: bool, issued_by: &str) -> Self {
+        Self { id, waypoints, looping, issued_by: issued_by.to_string(), status: None, reached: 0, detour: Vec::new(), issued: Instant::now() }
+    }
+
+    // e.g. "GoTo (200, 100)" or "FollowPath of 4 waypoints, looping"
+    pub fn describe(&self) -> String {
+        match self.waypoints.as_slice() {
+            [(x, y)] if !self.looping => format!("GoTo ({:.0}, {:.0})", x, y),
+            points => format!(
+                "FollowPath of {} waypoint(s){}",
+                points.len(),
+                if self.looping { ", looping" } else { "" }
+            ),
+        }
+    }
+
+    // e.g. "2/4 reached"
+    pub fn status_text(&self) -> String {
+        match self.status {
+            None => "sent".to_string(),
+            Some(MissionStatus::Completed) => "completed".to_string(),
+            Some(MissionStatus::Cancelled) => "cancelled".to_string(),
+            Some(_) if self.looping => format!("{} reached", self.reached),
+            Some(_) => format!("{}/{} reached", self.reached, self.waypoints.len()),
+        }
+    }
+
+    // Sent or under way
+    pub fn is_open(&self) -> bool {
+        !matches!(self.status, Some(MissionStatus::Completed | MissionStatus::Cancelled))
+    }
+
+    // Waypoints still ahead of the robot, in order, starting with any detour
+    pub fn remaining(&self) -> Vec<(f32, f32)> {
+        if !self.is_open() || self.waypoints.is_empty() {
+            return Vec::new();
+        }
+        let mut ahead = self.detour.clone();
+        if self.looping {
+            let start = self.reached % self.waypoints.len();
+            ahead.extend(self.waypoints.iter().cycle().skip(start).take(self.waypoints.len() + 1));
+        } else {
+            ahead.extend(self.waypoints.iter().skip(self.reached));
+        }
+        ahead
+    }
+
+    // Applies a progress report and returns the line to log
+    pub fn update(&mut self, robot: &str, progress: &MissionProgress) -> String {
+        // An update that reached no new waypoint is about the detour
+        let detour_news = matches!(progress.status, MissionStatus::InProgress) && progress.reached == self.reached;
+        let before = self.detour.len();
+        self.status = Some(progress.status);
+        self.reached = progress.reached;
+        // The robot drives the detour from the front
+        self.detour.drain(..before.saturating_sub(progress.detour));
+        let what = match progress.status {
+            MissionStatus::InProgress if detour_news && progress.detour >= before && before > 0 => {
+                format!("taking a detour via {} waypoint(s)", progress.detour)
+            }
+            MissionStatus::InProgress if detour_news && progress.detour == 0 => "detour done, back on its path".to_string(),
+            MissionStatus::InProgress if detour_news => format!("{} detour waypoint(s) left", progress.detour),
+            MissionStatus::Started => format!("started {}", self.describe()),
+            MissionStatus::InProgress if self.looping => format!("{} waypoints reached", progress.reached),
+            MissionStatus::InProgress => format!("reached waypoint {}/{}", progress.reached, self.waypoints.len()),
+            MissionStatus::Completed => format!("completed in {:.1} s", self.issued.elapsed().as_secs_f32()),
+            MissionStatus::Cancelled => format!("cancelled on the robot after {} waypoint(s)", progress.reached),
+        };
+        format!("Mission #{} ({}): {}", self.id, robot, what)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(status: MissionStatus, reached: usize) -> MissionProgress {
+        MissionProgress { mission: 1, status, reached, total: 0, detour: 0 }
+    }
+
+    fn path() -> Mission {
+        Mission::new(1, vec![(100.0, 100.0), (200.0, 100.0), (200.0, 200.0)], false, "alice")
+    }
+
+    #[test]
+    fn progress_reports_move_the_mission_along() {
+        let mut mission = path();
+        assert_eq!(mission.status_text(), "sent");
+        assert_eq!(mission.remaining().len(), 3);
+
+        let line = mission.update("R1", &progress(MissionStatus::Started, 0));
+        assert_eq!(line, "Mission #1 (R1): started FollowPath of 3 waypoint(s)");
+        let line = mission.update("R1", &progress(MissionStatus::InProgress, 2));
+        assert_eq!(line, "Mission #1 (R1): reached waypoint 2/3");
+        assert_eq!(mission.status_text(), "2/3 reached");
+        assert_eq!(mission.remaining(), vec![(200.0, 200.0)]);
+        assert!(mission.is_open());
+    }
+
+    #[test]
+    fn completed_and_cancelled_missions_are_closed() {
+        let mut mission = path();
+        mission.update("R1", &progress(MissionStatus::Completed, 3));
+        assert_eq!(mission.status_text(), "completed");
+        assert!(!mission.is_open());
+        assert!(mission.remaining().is_empty());
+
+        let mut mission = path();
+        let line = mission.update("R1", &progress(MissionStatus::Cancelled, 1));
+        assert_eq!(line, "Mission #1 (R1): cancelled on the robot after 1 waypoint(s)");
+        assert_eq!(mission.status_text(), "cancelled");
+        assert!(!mission.is_open());
+        assert!(mission.remaining().is_empty());
+    }
+
+    #[test]
+    fn looping_paths_keep_going_round() {
+        let mut mission = Mission::new(1, vec![(100.0, 100.0), (200.0, 100.0)], true, "alice");
+        assert_eq!(mission.describe(), "FollowPath of 2 waypoint(s), looping");
+        mission.update("R1", &progress(MissionStatus::InProgress, 5));
+        assert_eq!(mission.status_text(), "5 reached");
+        // Next stop, then once round the loop back to it
+        assert_eq!(mission.remaining(), vec![(200.0, 100.0), (100.0, 100.0), (200.0, 100.0)]);
+    }
+
+    #[test]
+    fn a_detour_is_driven_before_the_path_resumes() {
+        let mut mission = path();
+        mission.update("R1", &progress(MissionStatus::InProgress, 1));
+        mission.detour = vec![(150.0, 50.0), (200.0, 50.0)];
+        assert_eq!(mission.remaining()[..3], [(150.0, 50.0), (200.0, 50.0), (200.0, 100.0)]);
+
+        let line = mission.update("R1", &MissionProgress { detour: 1, ..progress(MissionStatus::InProgress, 1) });
+        assert_eq!(line, "Mission #1 (R1): 1 detour waypoint(s) left");
+        assert_eq!(mission.detour, vec![(200.0, 50.0)]);
+        let line = mission.update("R1", &progress(MissionStatus::InProgress, 1));
+        assert_eq!(line, "Mission #1 (R1): detour done, back on its path");
+        assert_eq!(mission.remaining(), vec![(200.0, 100.0), (200.0, 200.0)]);
+    }
+
+    #[test]
+    fn a_single_waypoint_is_a_goto() {
+        let mission = Mission::new(7, vec![(200.0, 100.0)], false, "alice");
+        assert_eq!(mission.describe(), "GoTo (200, 100)");
+    }
+}
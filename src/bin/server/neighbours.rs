@@ -0,0 +1,43 @@
+// Neighbour broadcasts: robots that asked for them in their Hello are sent,
+// a few times a second, every robot whose footprint comes within the radius of
+// theirs. The robots steer round each other with that, well before the safety
+// monitor has to stop anyone. Positions are the dead-reckoned estimates.
+use crate::SharedRobots;
+use assignment2::{Neighbour, ServerMessage};
+use std::thread;
+use std::time::Duration;
+
+pub const NEIGHBOUR_RADIUS: f32 = 150.0;
+const INTERVAL: Duration = Duration::from_millis(100);
+
+// Broadcasts until the process ends; a radius of 0 turns them off
+pub fn spawn(robots: SharedRobots, radius: f32) {
+    if radius <= 0.0 {
+        return;
+    }
+    thread::spawn(move || {
+        loop {
+            thread::sleep(INTERVAL);
+            let guard = robots.lock().unwrap();
+            let robots: Vec<_> = guard.values().map(|r| (r, r.outline())).collect();
+            for (robot, outline) in robots.iter().filter(|(r, _)| r.neighbours) {
+                let neighbours = robots
+                    .iter()
+                    .filter(|(other, near)| other.state.id != robot.state.id && outline.clearance(near) <= radius)
+                    .map(|(other, _)| {
+                        let pos = other.estimated_position().0;
+                        Neighbour {
+                            id: other.state.id.clone(),
+                            x: pos.x,
+                            y: pos.y,
+                            speed: other.state.speed,
+                            angle: other.state.angle,
+                            reach: other.footprint.reach(),
+                        }
+                    })
+                    .collect();
+                let _ = robot.tx_to_client.send(ServerMessage::Neighbours(neighbours));
+            }
+        }
+    });
+}
@@ -0,0 +1,197 @@
+// Operator accounts and roles for the GUI and the control API.
+//
+// The operators file is a JSON list; passwords are stored as salted
+// PBKDF2-HMAC-SHA256 hashes (`server --hash-password` prints one for a password
+// read from stdin), API tokens in plain text:
+//   [
+//     { "name": "alice", "role": "safety_supervisor", "password_hash": "pbkdf2-sha256$..." },
+//     { "name": "mes", "role": "operator", "api_token": "..." }
+//   ]
+use crate::auth::constant_time_eq;
+use ring::pbkdf2;
+use serde::Deserialize;
+use std::num::NonZeroU32;
+
+const HASH_SCHEME: &str = "pbkdf2-sha256";
+const HASH_ITERATIONS: u32 = 310_000;
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+// New hash for `password` with a random salt, as `pbkdf2-sha256$iterations$salt$hash`
+pub fn hash_password(password: &str) -> String {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let mut hash = [0u8; HASH_LEN];
+    let iterations = NonZeroU32::new(HASH_ITERATIONS).unwrap();
+    pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, &salt, password.as_bytes(), &mut hash);
+    format!("{}${}${}${}", HASH_SCHEME, HASH_ITERATIONS, to_hex(&salt), to_hex(&hash))
+}
+
+// Checks `password` against a stored hash; the comparison is constant time
+fn verify_password(stored: &str, password: &str) -> bool {
+    let Ok((iterations, salt, hash)) = parse_hash(stored) else { return false };
+    pbkdf2::verify(pbkdf2::PBKDF2_HMAC_SHA256, iterations, &salt, password.as_bytes(), &hash).is_ok()
+}
+
+fn parse_hash(stored: &str) -> Result<(NonZeroU32, Vec<u8>, Vec<u8>), String> {
+    let parts: Vec<&str> = stored.split('$').collect();
+    let [scheme, iterations, salt, hash] = parts[..] else {
+        return Err("expected pbkdf2-sha256$iterations$salt$hash".into());
+    };
+    if scheme != HASH_SCHEME {
+        return Err(format!("unsupported scheme '{}'", scheme));
+    }
+    let iterations = iterations
+        .parse()
+        .ok()
+        .and_then(NonZeroU32::new)
+        .ok_or_else(|| format!("invalid iteration count '{}'", iterations))?;
+    let salt = from_hex(salt).ok_or("salt is not hex")?;
+    let hash = from_hex(hash).filter(|h| !h.is_empty()).ok_or("hash is not hex")?;
+    Ok((iterations, salt, hash))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect()
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    // Can watch, cannot command
+    Viewer,
+    // Can stop and resume robots
+    Operator,
+    // Can also resume after an emergency stop and change speed limits
+    SafetySupervisor,
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::SafetySupervisor => "safety supervisor",
+        })
+    }
+}
+
+// Who issued a command, and through which surface ("GUI", "API")
+#[derive(Clone, Debug)]
+pub struct Operator {
+    pub name: String,
+    pub role: Role,
+    pub source: &'static str,
+}
+
+#[derive(Deserialize)]
+struct Account {
+    name: String,
+    role: Role,
+    #[serde(default)]
+    password_hash: Option<String>,
+    #[serde(default)]
+    api_token: Option<String>,
+}
+
+pub struct Operators {
+    accounts: Vec<Account>,
+    // Without an operators file the console is trusted as before
+    open_console: bool,
+}
+
+impl Operators {
+    // No operators file: the local GUI acts as a safety supervisor and the
+    // legacy `--api-token` (if any) grants the same rights to API callers.
+    pub fn unrestricted(api_token: Option<String>) -> Self {
+        let accounts = api_token
+            .map(|token| Account {
+                name: "api".into(),
+                role: Role::SafetySupervisor,
+                password_hash: None,
+                api_token: Some(token),
+            })
+            .into_iter()
+            .collect();
+        Self { accounts, open_console: true }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let accounts: Vec<Account> =
+            serde_json::from_str(&text).map_err(|e| format!("Invalid operators file {}: {}", path, e))?;
+        for account in &accounts {
+            if let Some(hash) = &account.password_hash {
+                parse_hash(hash).map_err(|e| format!("Invalid password_hash for '{}' in {}: {}", account.name, path, e))?;
+            }
+        }
+        Ok(Self { accounts, open_console: false })
+    }
+
+    // Operator the GUI starts as, if login is not required
+    pub fn console_default(&self) -> Option<Operator> {
+        self.open_console.then(|| Operator { name: "console".into(), role: Role::SafetySupervisor, source: "GUI" })
+    }
+
+    pub fn has_api_tokens(&self) -> bool {
+        self.accounts.iter().any(|a| a.api_token.is_some())
+    }
+
+    pub fn login(&self, name: &str, password: &str) -> Result<Operator, String> {
+        self.accounts
+            .iter()
+            .find(|a| a.name == name && a.password_hash.as_deref().is_some_and(|h| verify_password(h, password)))
+            .map(|a| Operator { name: a.name.clone(), role: a.role, source: "GUI" })
+            .ok_or_else(|| "Invalid operator name or password".to_string())
+    }
+
+    pub fn by_token(&self, token: &str) -> Option<Operator> {
+        self.accounts
+            .iter()
+            .find(|a| a.api_token.as_deref().is_some_and(|t| constant_time_eq(t.as_bytes(), token.as_bytes())))
+            .map(|a| Operator { name: a.name.clone(), role: a.role, source: "API" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operators(accounts: &str) -> Result<Operators, String> {
+        let path = std::env::temp_dir().join(format!("operators-{}-{}.json", std::process::id(), rand::random::<u32>()));
+        std::fs::write(&path, accounts).unwrap();
+        let loaded = Operators::load(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        loaded
+    }
+
+    #[test]
+    fn hashes_are_salted_and_verify() {
+        let first = hash_password("secret");
+        assert_ne!(first, hash_password("secret"));
+        assert!(verify_password(&first, "secret"));
+        assert!(!verify_password(&first, "Secret"));
+        assert!(!verify_password("not a hash", "secret"));
+    }
+
+    #[test]
+    fn login_checks_the_stored_hash() {
+        let accounts = format!(r#"[{{"name": "alice", "role": "operator", "password_hash": "{}"}}]"#, hash_password("pw"));
+        let operators = operators(&accounts).unwrap();
+        assert_eq!(operators.login("alice", "pw").unwrap().role, Role::Operator);
+        assert!(operators.login("alice", "wrong").is_err());
+        assert!(operators.login("bob", "pw").is_err());
+    }
+
+    #[test]
+    fn malformed_hashes_are_refused() {
+        assert!(operators(r#"[{"name": "a", "role": "viewer", "password_hash": "pbkdf2-sha256$0$00$00"}]"#).is_err());
+        assert!(operators(r#"[{"name": "a", "role": "viewer", "password_hash": "md5$1$00$00"}]"#).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+// Path planning around the obstacles and keep-out zones of the workspace map.
+//
+// The workspace is cut into CELL-sized squares. A cell is blocked when its
+// centre is too close to a zone or the workspace edge for the robot: its reach
+// plus half a cell diagonal plus MARGIN, so every point of a free cell keeps the
+// robot's footprint clear. A* over the free cells (8-connected, no cutting
+// corners) finds a chain of cells, which is then cut down to the fewest
+// waypoints that can still see each other in a straight line.
+//
+// A robot that starts inside the inflated area (parked close to a wall, say)
+// drives straight out to the nearest free cell first, and the path ends with a
+// straight run from the nearest free cell to the goal, as long as the goal
+// itself is not inside a zone.
+use crate::map::WorkspaceMap;
+use assignment2::footprint::Placed;
+use assignment2::{BOUNDARY_HEIGHT, BOUNDARY_WIDTH};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+pub const CELL: f32 = 10.0;
+// Extra clearance kept from zones and the edge, px
+const MARGIN: f32 = 5.0;
+// A* step costs: straight and diagonal, roughly 10 and 10 * sqrt(2)
+const STRAIGHT: u32 = 10;
+const DIAGONAL: u32 = 14;
+
+pub struct Grid {
+    cols: usize,
+    rows: usize,
+    blocked: Vec<bool>,
+    // Clearance a free cell centre keeps from anything blocked
+    inflation: f32,
+}
+
+impl Grid {
+    // Cells a robot of the given reach cannot occupy
+    pub fn new(map: &WorkspaceMap, reach: f32) -> Self {
+        let cols = (BOUNDARY_WIDTH / CELL).ceil() as usize;
+        let rows = (BOUNDARY_HEIGHT / CELL).ceil() as usize;
+        let inflation = reach + CELL * std::f32::consts::FRAC_1_SQRT_2 + MARGIN;
+        let outlines: Vec<Placed> = map.zones().map(|(zone, _)| zone.outline()).collect();
+        let blocked = (0..rows * cols)
+            .map(|i| {
+                let (x, y) = centre(i % cols, i / cols);
+                let near_edge = x.min(y).min(BOUNDARY_WIDTH - x).min(BOUNDARY_HEIGHT - y) < reach + CELL / 2.0 + MARGIN;
+                let disc = Placed::Circle { centre: (x, y), radius: inflation };
+                near_edge || outlines.iter().any(|zone| disc.clearance(zone) == 0.0)
+            })
+            .collect();
+        Self { cols, rows, blocked, inflation }
+    }
+
+    // Also keeps clear of a disc, e.g. another robot and the way it is going
+    pub fn block(&mut self, (x, y): (f32, f32), radius: f32) {
+        let reach = radius + self.inflation;
+        for i in 0..self.blocked.len() {
+            let (cx, cy) = self.centre_of(i);
+            if (cx - x).hypot(cy - y) < reach {
+                self.blocked[i] = true;
+            }
+        }
+    }
+
+    // Centre of the free cell nearest to `from`, to step aside to
+    pub fn escape(&self, from: (f32, f32)) -> Option<(f32, f32)> {
+        self.nearest_free(self.index_of(from)).map(|i| self.centre_of(i))
+    }
+
+    fn index_of(&self, (x, y): (f32, f32)) -> usize {
+        let col = ((x / CELL) as usize).min(self.cols - 1);
+        let row = ((y / CELL) as usize).min(self.rows - 1);
+        row * self.cols + col
+    }
+
+    fn centre_of(&self, i: usize) -> (f32, f32) {
+        centre(i % self.cols, i / self.cols)
+    }
+
+    // Free neighbours and the cost of stepping to each
+    fn neighbours(&self, i: usize) -> impl Iterator<Item = (usize, u32)> + '_ {
+        let (col, row) = ((i % self.cols) as isize, (i / self.cols) as isize);
+        let free = move |c: isize, r: isize| {
+            (0..self.cols as isize).contains(&c)
+                && (0..self.rows as isize).contains(&r)
+                && !self.blocked[r as usize * self.cols + c as usize]
+        };
+        [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)].into_iter().filter_map(move |(dc, dr)| {
+            let (c, r) = (col + dc, row + dr);
+            let diagonal = dc != 0 && dr != 0;
+            // Diagonal steps only when both cells beside them are free too
+            let ok = free(c, r) && (!diagonal || (free(col + dc, row) && free(col, row + dr)));
+            ok.then(|| (r as usize * self.cols + c as usize, if diagonal { DIAGONAL } else { STRAIGHT }))
+        })
+    }
+
+    // Octile distance, a lower bound on the cost between two cells
+    fn heuristic(&self, a: usize, b: usize) -> u32 {
+        let dc = (a % self.cols).abs_diff(b % self.cols) as u32;
+        let dr = (a / self.cols).abs_diff(b / self.cols) as u32;
+        STRAIGHT * dc.max(dr) + (DIAGONAL - STRAIGHT) * dc.min(dr)
+    }
+
+    // Closest free cell, by steps through the grid
+    fn nearest_free(&self, from: usize) -> Option<usize> {
+        let mut seen = vec![false; self.blocked.len()];
+        let mut queue = VecDeque::from([from]);
+        seen[from] = true;
+        while let Some(i) = queue.pop_front() {
+            if !self.blocked[i] {
+                return Some(i);
+            }
+            let (col, row) = (i % self.cols, i / self.cols);
+            let next = [
+                (col > 0).then(|| i - 1),
+                (col + 1 < self.cols).then(|| i + 1),
+                (row > 0).then(|| i - self.cols),
+                (row + 1 < self.rows).then(|| i + self.cols),
+            ];
+            for j in next.into_iter().flatten() {
+                if !seen[j] {
+                    seen[j] = true;
+                    queue.push_back(j);
+                }
+            }
+        }
+        None
+    }
+
+    fn search(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut cost = vec![u32::MAX; self.blocked.len()];
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::from([Reverse((self.heuristic(from, to), from))]);
+        cost[from] = 0;
+        while let Some(Reverse((_, i))) = open.pop() {
+            if i == to {
+                let mut path = vec![to];
+                while let Some(&last) = path.last().filter(|&&c| c != from) {
+                    path.push(came_from[last]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for (j, step) in self.neighbours(i) {
+                let c = cost[i] + step;
+                if c < cost[j] {
+                    cost[j] = c;
+                    came_from[j] = i;
+                    open.push(Reverse((c + self.heuristic(j, to), j)));
+                }
+            }
+        }
+        None
+    }
+
+    // True when the straight line from a to b only crosses free cells
+    fn visible(&self, a: (f32, f32), b: (f32, f32)) -> bool {
+        let steps = ((b.0 - a.0).hypot(b.1 - a.1) / (CELL / 2.0)).ceil().max(1.0) as usize;
+        (0..=steps).all(|k| {
+            let t = k as f32 / steps as f32;
+            !self.blocked[self.index_of((a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t))]
+        })
+    }
+
+    // Waypoints from `start` to `goal`, ending on the goal itself
+    pub fn plan(&self, start: (f32, f32), goal: (f32, f32)) -> Result<Vec<(f32, f32)>, String> {
+        let no_path = || format!("No path from ({:.0}, {:.0}) to ({:.0}, {:.0})", start.0, start.1, goal.0, goal.1);
+        let from = self.nearest_free(self.index_of(start)).ok_or_else(no_path)?;
+        let to = self.nearest_free(self.index_of(goal)).ok_or_else(no_path)?;
+        let cells = self.search(from, to).ok_or_else(no_path)?;
+
+        // Points to pass through: out to the first free cell, along the cells, in to the goal
+        let mut points = vec![start];
+        points.extend(cells.iter().map(|&i| self.centre_of(i)));
+        points.push(goal);
+
+        // Keep a point only where the one after it cannot be seen from the last one kept
+        let mut waypoints: Vec<(f32, f32)> = Vec::new();
+        let mut anchor = start;
+        for (k, &point) in points.iter().enumerate().skip(1) {
+            let next = points.get(k + 1);
+            if next.is_none_or(|&n| !self.visible(anchor, n)) {
+                waypoints.push(point);
+                anchor = point;
+            }
+        }
+        Ok(waypoints)
+    }
+}
+
+fn centre(col: usize, row: usize) -> (f32, f32) {
+    ((col as f32 + 0.5) * CELL, (row as f32 + 0.5) * CELL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Zone;
+
+    const REACH: f32 = 10.0;
+
+    // A wall across the middle of the workspace, leaving a gap at the bottom
+    fn wall() -> Zone {
+        Zone::Rect { x: 290.0, y: 0.0, width: 20.0, height: 300.0 }
+    }
+
+    // True when a robot of REACH driving the waypoints never touches a zone
+    fn stays_clear(map: &WorkspaceMap, start: (f32, f32), waypoints: &[(f32, f32)]) -> bool {
+        let mut from = start;
+        waypoints.iter().all(|&to| {
+            let steps = ((to.0 - from.0).hypot(to.1 - from.1) / 2.0).ceil().max(1.0) as usize;
+            let clear = (0..=steps).all(|k| {
+                let t = k as f32 / steps as f32;
+                let centre = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+                let robot = Placed::Circle { centre, radius: REACH };
+                map.zones().all(|(zone, _)| robot.clearance(&zone.outline()) > 0.0)
+            });
+            from = to;
+            clear
+        })
+    }
+
+    #[test]
+    fn open_floor_is_one_straight_run() {
+        let grid = Grid::new(&WorkspaceMap::default(), REACH);
+        assert_eq!(grid.plan((50.0, 50.0), (500.0, 300.0)), Ok(vec![(500.0, 300.0)]));
+    }
+
+    #[test]
+    fn goes_round_an_obstacle() {
+        let map = WorkspaceMap { obstacles: vec![wall()], ..Default::default() };
+        let (start, goal) = ((100.0, 100.0), (500.0, 100.0));
+        let waypoints = Grid::new(&map, REACH).plan(start, goal).unwrap();
+        assert!(waypoints.len() > 1);
+        assert_eq!(waypoints.last(), Some(&goal));
+        assert!(stays_clear(&map, start, &waypoints));
+        // Through the gap below the wall
+        assert!(waypoints.iter().any(|&(_, y)| y > 300.0));
+    }
+
+    #[test]
+    fn keeps_off_keep_out_zones() {
+        let zone = Zone::Polygon { points: vec![(250.0, 150.0), (350.0, 150.0), (350.0, 250.0), (250.0, 250.0)] };
+        let map = WorkspaceMap { keep_out: vec![zone], ..Default::default() };
+        let (start, goal) = ((100.0, 200.0), (500.0, 200.0));
+        let waypoints = Grid::new(&map, REACH).plan(start, goal).unwrap();
+        assert!(waypoints.len() > 1);
+        assert!(stays_clear(&map, start, &waypoints));
+    }
+
+    #[test]
+    fn wider_robots_need_wider_gaps() {
+        // The 40 px gap under the wall fits a small robot but not a large one
+        let wall = Zone::Rect { x: 290.0, y: 0.0, width: 20.0, height: BOUNDARY_HEIGHT - 40.0 };
+        let map = WorkspaceMap { obstacles: vec![wall], ..Default::default() };
+        let (start, goal) = ((100.0, 100.0), (500.0, 100.0));
+        assert!(Grid::new(&map, 2.0).plan(start, goal).is_ok());
+        assert!(Grid::new(&map, 30.0).plan(start, goal).is_err());
+    }
+
+    #[test]
+    fn no_path_through_a_closed_wall() {
+        let wall = Zone::Rect { x: 290.0, y: 0.0, width: 20.0, height: BOUNDARY_HEIGHT };
+        let map = WorkspaceMap { obstacles: vec![wall], ..Default::default() };
+        let result = Grid::new(&map, REACH).plan((100.0, 100.0), (500.0, 100.0));
+        assert!(result.unwrap_err().starts_with("No path"));
+    }
+
+    #[test]
+    fn blocked_discs_are_avoided_too() {
+        let mut grid = Grid::new(&WorkspaceMap::default(), REACH);
+        grid.block((300.0, 200.0), 20.0);
+        let waypoints = grid.plan((100.0, 200.0), (500.0, 200.0)).unwrap();
+        assert!(waypoints.len() > 1);
+        assert!(waypoints.iter().all(|&(x, y)| (x - 300.0).hypot(y - 200.0) > 20.0 + REACH));
+    }
+}
@@ -0,0 +1,164 @@
+// Safety monitor: collision and boundary checks on a fixed-rate thread,
+// so stops are enforced even when the GUI is not repainting.
+use crate::{RobotData, SharedRobots};
+use assignment2::footprint::Placed;
+use assignment2::{now_ms, ProximityPair, SafetyEvent, ServerMessage, BOUNDARY_HEIGHT, BOUNDARY_WIDTH};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const SAFE_DISTANCE: f32 = 30.0; // Gap between footprints to trigger collision warning
+pub const WARNING_DISTANCE: f32 = SAFE_DISTANCE * 1.5; // Heatmap lines below this
+const CHECK_INTERVAL: Duration = Duration::from_millis(30);
+// An active robot whose latest position is older than this is stopped
+pub const STALE_AFTER: Duration = Duration::from_secs(1);
+const MAX_EVENTS: usize = 50;
+// A robot that keeps tripping the same check is only logged once per interval
+const EVENT_REPEAT: Duration = Duration::from_secs(1);
+// Closest a robot with right of way may pass one that gave way to it, px
+const PASSING_GAP: f32 = SAFE_DISTANCE / 3.0;
+// Spacing of the footprints sampled along a robot's way ahead, px
+const SAMPLE_STEP: f32 = 2.0;
+
+#[derive(Default)]
+pub struct SafetyReport {
+    pub proximity: Vec<ProximityPair>,
+    pub events: VecDeque<SafetyEvent>,
+}
+
+pub type SharedSafety = Arc<Mutex<SafetyReport>>;
+
+pub fn spawn_monitor(robots: SharedRobots, tx_log: mpsc::Sender<String>) -> SharedSafety {
+    let safety: SharedSafety = Arc::new(Mutex::new(SafetyReport::default()));
+    let report = safety.clone();
+
+    thread::spawn(move || {
+        let mut last_logged: HashMap<String, Instant> = HashMap::new();
+        loop {
+            thread::sleep(CHECK_INTERVAL);
+            let guard = robots.lock().unwrap();
+            let (proximity, ids_to_stop) = check(&guard);
+
+            let mut new_events = Vec::new();
+            for (id, reason) in ids_to_stop {
+                if let Some(robot) = guard.get(&id) {
+                    // FIXED: Only send stop if the robot is actually active
+                    // This prevents spamming the log if the robot is already stopped.
+                    // A stop still waiting for its Ack is being retransmitted already, and one
+                    // acknowledged since the latest telemetry has not shown up in it yet.
+                    if robot.state.active && !robot.outbox.lock().unwrap().stop_in_effect(robot.last_seen) {
+                        let _ = robot.tx_to_client.send(ServerMessage::ForceStop);
+                        let _ = robot.tx_to_client.send(ServerMessage::Warning(format!("{}!", reason)));
+
+                        let repeat = last_logged.get(&id).is_some_and(|t| t.elapsed() < EVENT_REPEAT);
+                        if !repeat {
+                            last_logged.insert(id.clone(), Instant::now());
+                            new_events.push(SafetyEvent { time_ms: now_ms(), robot: id, reason: reason.into() });
+                        }
+                    }
+                }
+            }
+            drop(guard);
+
+            let mut report = report.lock().unwrap();
+            report.proximity = proximity;
+            for event in new_events {
+                let _ = tx_log.send(format!("SAFETY STOP {}: {}", event.robot, event.reason));
+                report.events.push_back(event);
+                if report.events.len() > MAX_EVENTS {
+                    report.events.pop_front();
+                }
+            }
+        }
+    });
+
+    safety
+}
+
+// How far a robot may have moved since its latest position was measured.
+// Past STALE_AFTER it is being stopped anyway.
+fn stale_margin(robot: &RobotData) -> f32 {
+    robot.state.speed.abs() * robot.data_age().min(STALE_AFTER).as_secs_f32()
+}
+
+// Smallest gap between the robot's footprint and `other` over its next
+// `distance` px straight on
+fn closest_approach(robot: &RobotData, other: &Placed, distance: f32) -> f32 {
+    let pos = robot.estimated_position().0;
+    let direction = if robot.state.speed < 0.0 { -1.0 } else { 1.0 };
+    let (dx, dy) = (direction * robot.state.angle.cos(), direction * robot.state.angle.sin());
+    // Nothing in the workspace is further away than its diagonal
+    let distance = distance.min(BOUNDARY_WIDTH.hypot(BOUNDARY_HEIGHT));
+    let steps = (distance / SAMPLE_STEP).ceil() as usize;
+    (0..=steps)
+        .map(|k| {
+            let d = k as f32 * SAMPLE_STEP;
+            robot.footprint.place(pos.x + d * dx, pos.y + d * dy, robot.state.body_heading()).clearance(other)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+// Returns robot pairs within the warning distance and the robots that must be stopped.
+// Distances are measured between footprints, widened by each robot's braking distance and by how far it
+// could have moved since its position was measured, and robots that stopped
+// reporting are stopped.
+//
+// Of two robots too close together, only the one with the lower priority is
+// stopped, as long as that is enough: its position is fresh, and going straight
+// on for its braking distance plus SAFE_DISTANCE, the other keeps PASSING_GAP
+// clear of it, wherever it comes to rest. Equal priorities stop both.
+fn check(guard: &HashMap<String, RobotData>) -> (Vec<ProximityPair>, Vec<(String, &'static str)>) {
+    let mut proximity = Vec::new();
+    let mut ids_to_stop = Vec::new();
+    let keys: Vec<&String> = guard.keys().collect();
+    // Footprints at the dead-reckoned positions, so checks do not lag behind the robots
+    let outlines: Vec<_> = keys.iter().map(|k| guard[*k].outline()).collect();
+
+    // Check collisions between pairs
+    for i in 0..keys.len() {
+        for j in (i + 1)..keys.len() {
+            // Both robots must be able to brake to rest before they get too close
+            let (a, b) = (&guard[keys[i]], &guard[keys[j]]);
+            let margin = stale_margin(a) + stale_margin(b) + a.state.stopping_distance + b.state.stopping_distance;
+
+            let dist = outlines[i].clearance(&outlines[j]);
+
+            // Heatmap / Proximity Warning
+            if dist < WARNING_DISTANCE {
+                proximity.push(ProximityPair { a: keys[i].clone(), b: keys[j].clone(), distance: dist });
+            }
+
+            if dist < SAFE_DISTANCE + margin {
+                let (high, low) = if a.priority >= b.priority { (i, j) } else { (j, i) };
+                let (higher, lower) = (&guard[keys[high]], &guard[keys[low]]);
+                let way_ahead = higher.state.stopping_distance + stale_margin(higher) + SAFE_DISTANCE;
+                let lower_drift = lower.state.stopping_distance + stale_margin(lower);
+                let give_way = higher.priority > lower.priority
+                    && lower.data_age() <= STALE_AFTER
+                    && closest_approach(higher, &outlines[low], way_ahead) >= PASSING_GAP + lower_drift;
+                if give_way {
+                    ids_to_stop.push((keys[low].clone(), "Collision risk, giving way"));
+                } else {
+                    ids_to_stop.push((keys[i].clone(), "Collision risk"));
+                    ids_to_stop.push((keys[j].clone(), "Collision risk"));
+                }
+            }
+        }
+    }
+
+    // Check Boundaries
+    for (id, outline) in keys.iter().zip(&outlines) {
+        let robot = &guard[*id];
+        let (x0, y0, x1, y1) = outline.bounds();
+        let edge = stale_margin(robot) + robot.state.stopping_distance;
+        if x0 < edge || y0 < edge || x1 > BOUNDARY_WIDTH - edge || y1 > BOUNDARY_HEIGHT - edge {
+            ids_to_stop.push(((*id).clone(), "Boundary breach"));
+        }
+        if robot.data_age() > STALE_AFTER {
+            ids_to_stop.push(((*id).clone(), "Telemetry stale"));
+        }
+    }
+
+    (proximity, ids_to_stop)
+}
@@ -0,0 +1,77 @@
+// Live fleet telemetry over WebSocket for external dashboards.
+// Upgraded from `GET /ws` on the control API port; `?hz=N` overrides the default push rate.
+use crate::safety::SharedSafety;
+use crate::SharedRobots;
+use assignment2::{now_ms, FleetSnapshot, RobotState};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+pub const MAX_HZ: f32 = 50.0;
+
+pub fn snapshot(robots: &SharedRobots, safety: &SharedSafety) -> FleetSnapshot {
+    // Robots are sent at their dead-reckoned positions, like the server view
+    let mut states = Vec::new();
+    let mut predicted = Vec::new();
+    let mut footprints = BTreeMap::new();
+    for robot in robots.lock().unwrap().values() {
+        footprints.insert(robot.state.id.clone(), robot.footprint.clone());
+        let (pos, is_predicted) = robot.estimated_position();
+        states.push(RobotState { x: pos.x, y: pos.y, ..robot.state.clone() });
+        if is_predicted {
+            predicted.push(robot.state.id.clone());
+        }
+    }
+    states.sort_by(|a, b| a.id.cmp(&b.id));
+    predicted.sort();
+    let report = safety.lock().unwrap();
+    FleetSnapshot {
+        time_ms: now_ms(),
+        robots: states,
+        predicted,
+        footprints,
+        proximity: report.proximity.clone(),
+        events: report.events.iter().cloned().collect(),
+    }
+}
+
+// Completes the WebSocket handshake for an already-parsed upgrade request,
+// then pushes snapshots until the client goes away.
+pub fn serve(mut stream: TcpStream, key: &str, hz: f32, robots: SharedRobots, safety: SharedSafety) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        tungstenite::handshake::derive_accept_key(key.as_bytes())
+    )?;
+    stream.flush()?;
+
+    // Short read timeout so we can answer pings/closes between pushes
+    stream.set_read_timeout(Some(Duration::from_millis(1)))?;
+    let mut ws = WebSocket::from_raw_socket(stream, Role::Server, None);
+    let period = Duration::from_secs_f32(1.0 / hz.clamp(1.0, MAX_HZ));
+
+    loop {
+        let started = Instant::now();
+        let json = serde_json::to_string(&snapshot(&robots, &safety)).unwrap();
+        if ws.send(Message::text(json)).is_err() {
+            break;
+        }
+
+        while started.elapsed() < period {
+            match ws.read() {
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) =>
+                {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                Err(_) => return Ok(()),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,422 @@
+// Traffic manager: keeps robots on missions out of each other's way, so the
+// safety monitor does not have to stop them nose to nose.
+//
+// The workspace is divided into CELL-sized squares. Every tick each robot
+// holds the cells under its footprint, widened by half of SAFE_DISTANCE. A
+// robot on a mission also asks for the cells along its path ahead, far enough
+// to brake and then LOOKAHEAD more. Requests are granted by right-of-way:
+// the highest priority first, then robots already under way keep it, then the
+// oldest mission goes first. A
+// robot whose path ahead runs into cells held by another is sent Hold, and
+// Release once they are free. Robots driven by hand cannot be held, so the
+// cells ahead of them are given out first.
+//
+// Holds can deadlock, e.g. two robots facing each other in a corridor. Once a
+// robot has been held for PATIENCE, the robots it waits for are followed: if
+// they lead back to it, the robot in that cycle with the least right-of-way
+// is sent a Detour planned around the others; if it waits for robots that are
+// not moving, it takes the detour itself. With no way round, the detour steps
+// aside to the nearest free spot. A robot sent a detour yields to everyone
+// for a while, so the robots it made way for go first.
+use crate::control::FleetControl;
+use crate::planner::Grid;
+use crate::safety::{SAFE_DISTANCE, STALE_AFTER};
+use crate::RobotData;
+use assignment2::{ServerMessage, BOUNDARY_HEIGHT, BOUNDARY_WIDTH};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const CELL: f32 = 20.0;
+// Path reserved beyond the stopping distance, px
+const LOOKAHEAD: f32 = 40.0;
+const TICK: Duration = Duration::from_millis(100);
+// Held this long before the manager looks for a deadlock
+const PATIENCE: Duration = Duration::from_secs(3);
+// After a detour, the robot gives way to everyone for this long
+const YIELD_FOR: Duration = Duration::from_secs(10);
+
+// Column and row of a cell
+pub type Cell = (i32, i32);
+
+#[derive(Default)]
+pub struct TrafficReport {
+    // Robot holding each cell
+    pub reserved: HashMap<Cell, String>,
+    // Held robots and the robots they wait for
+    pub held: BTreeMap<String, Vec<String>>,
+}
+
+pub type SharedTraffic = Arc<Mutex<TrafficReport>>;
+
+// One robot's part in the traffic picture for a tick
+struct Claim {
+    id: String,
+    position: (f32, f32),
+    reach: f32,
+    priority: u8,
+    moving: bool,
+    // Open mission of a robot that is under way; only these can be held
+    mission: Option<u64>,
+    // Driving a detour already
+    detouring: bool,
+    // Next waypoint of the mission after any detour
+    next_waypoint: Option<(f32, f32)>,
+    // Points along the path ahead, out to the braking distance plus LOOKAHEAD
+    ahead: Vec<(f32, f32)>,
+    occupied: HashSet<Cell>,
+}
+
+impl Claim {
+    fn new(robot: &RobotData) -> Self {
+        let state = &robot.state;
+        let pos = robot.estimated_position().0;
+        let position = (pos.x, pos.y);
+        let fresh = robot.data_age() <= STALE_AFTER;
+        let mission = robot.mission.as_ref().filter(|m| m.is_open() && state.active && fresh);
+        let distance = state.stopping_distance + LOOKAHEAD;
+        let ahead = match mission {
+            Some(m) => path_ahead(position, &m.remaining(), distance),
+            // Straight on, for a robot driven by hand
+            None if state.active && fresh && state.speed > 0.0 => {
+                let end = (pos.x + distance * state.angle.cos(), pos.y + distance * state.angle.sin());
+                path_ahead(position, &[end], distance)
+            }
+            None => Vec::new(),
+        };
+        let reach = robot.footprint.reach();
+        Self {
+            id: state.id.clone(),
+            position,
+            reach,
+            priority: robot.priority,
+            moving: state.active && state.speed > 0.0,
+            mission: mission.map(|m| m.id),
+            detouring: mission.is_some_and(|m| !m.detour.is_empty()),
+            next_waypoint: mission.and_then(|m| m.remaining().get(m.detour.len()).copied()),
+            ahead,
+            occupied: cells_around(position, reach + SAFE_DISTANCE / 2.0).into_iter().collect(),
+        }
+    }
+
+    // Cells along the path ahead that the robot does not stand on yet
+    fn wanted(&self) -> HashSet<Cell> {
+        let radius = self.reach + SAFE_DISTANCE / 2.0;
+        self.ahead.iter().flat_map(|&p| cells_around(p, radius)).filter(|c| !self.occupied.contains(c)).collect()
+    }
+}
+
+struct Manager {
+    control: FleetControl,
+    tx_log: mpsc::Sender<String>,
+    report: SharedTraffic,
+    // Robots under way last tick keep their right-of-way
+    granted: HashSet<String>,
+    held_since: HashMap<String, Instant>,
+    // Robots that took a detour, and when
+    yielding: HashMap<String, Instant>,
+}
+
+pub fn spawn(control: FleetControl, tx_log: mpsc::Sender<String>) -> SharedTraffic {
+    let report: SharedTraffic = Arc::new(Mutex::new(TrafficReport::default()));
+    let mut manager = Manager {
+        control,
+        tx_log,
+        report: report.clone(),
+        granted: HashSet::new(),
+        held_since: HashMap::new(),
+        yielding: HashMap::new(),
+    };
+    thread::spawn(move || {
+        loop {
+            thread::sleep(TICK);
+            manager.tick();
+        }
+    });
+    report
+}
+
+impl Manager {
+    fn tick(&mut self) {
+        let robots = self.control.robots().clone();
+        let mut guard = robots.lock().unwrap();
+        let claims: Vec<Claim> = guard.values().map(Claim::new).collect();
+        self.yielding.retain(|_, since| since.elapsed() < YIELD_FOR);
+
+        // Nobody may take the cells a robot stands on
+        let mut reserved: HashMap<Cell, String> = HashMap::new();
+        for claim in &claims {
+            for cell in &claim.occupied {
+                reserved.entry(*cell).or_insert_with(|| claim.id.clone());
+            }
+        }
+
+        // Then the paths ahead, in order of right-of-way
+        let mut order: Vec<&Claim> = claims.iter().filter(|c| !c.ahead.is_empty()).collect();
+        order.sort_by_key(|c| self.right_of_way(c));
+        let mut held: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut granted = HashSet::new();
+        for claim in order {
+            let wanted = claim.wanted();
+            let mut waits: Vec<String> =
+                wanted.iter().filter_map(|cell| reserved.get(cell)).filter(|owner| **owner != claim.id).cloned().collect();
+            waits.sort();
+            waits.dedup();
+            if waits.is_empty() || claim.mission.is_none() {
+                for cell in wanted {
+                    reserved.entry(cell).or_insert_with(|| claim.id.clone());
+                }
+                granted.insert(claim.id.clone());
+            } else {
+                held.insert(claim.id.clone(), waits);
+            }
+        }
+        self.granted = granted;
+
+        // Tell robots about changes only
+        for (id, waits) in &held {
+            if !self.held_since.contains_key(id)
+                && let Some(robot) = guard.get(id)
+            {
+                let _ = robot.tx_to_client.send(ServerMessage::Hold);
+                let _ = self.tx_log.send(format!("TRAFFIC: {} held, waiting for {}", id, waits.join(", ")));
+                self.held_since.insert(id.clone(), Instant::now());
+            }
+        }
+        let released: Vec<String> = self.held_since.keys().filter(|id| !held.contains_key(*id)).cloned().collect();
+        for id in released {
+            let since = self.held_since.remove(&id).unwrap();
+            if let Some(robot) = guard.get(&id) {
+                let _ = robot.tx_to_client.send(ServerMessage::Release);
+                let _ = self.tx_log.send(format!("TRAFFIC: {} released after {:.1} s", id, since.elapsed().as_secs_f32()));
+            }
+        }
+
+        // Deadlocks: robots waiting on each other, or on robots that are not going anywhere
+        let claim = |id: &str| claims.iter().find(|c| c.id == id);
+        for (id, waits) in &held {
+            if self.held_since[id].elapsed() < PATIENCE {
+                continue;
+            }
+            let (loser, why) = match find_cycle(&held, id) {
+                Some(cycle) => {
+                    let loser = cycle.iter().filter_map(|id| claim(id)).max_by_key(|c| self.right_of_way(c));
+                    (loser, format!("deadlock between {}", cycle.join(", ")))
+                }
+                None if waits.iter().all(|w| claim(w).is_none_or(|c| !c.moving)) => {
+                    (claim(id), format!("{} waiting on {} standing still", id, waits.join(", ")))
+                }
+                None => continue,
+            };
+            if let Some(loser) = loser.filter(|l| !l.detouring && !self.yielding.contains_key(&l.id)) {
+                self.detour(&mut guard, &claims, loser, waits, &why);
+            }
+        }
+        drop(guard);
+
+        let mut report = self.report.lock().unwrap();
+        report.reserved = reserved;
+        report.held = held;
+    }
+
+    // Sort key: lowest goes first. Robots driven by hand, then the highest
+    // priority; among equals, robots that took a detour go last, then robots
+    // under way go first, then the oldest mission.
+    fn right_of_way(&self, claim: &Claim) -> (bool, Reverse<u8>, bool, bool, u64, String) {
+        (
+            claim.mission.is_some(),
+            Reverse(claim.priority),
+            self.yielding.contains_key(&claim.id),
+            !self.granted.contains(&claim.id),
+            claim.mission.unwrap_or(0),
+            claim.id.clone(),
+        )
+    }
+
+    // Sends `loser` round the robots in its way, or aside if there is no way round
+    fn detour(
+        &mut self,
+        guard: &mut HashMap<String, RobotData>,
+        claims: &[Claim],
+        loser: &Claim,
+        waits: &[String],
+        why: &str,
+    ) {
+        let (Some(mission), Some(next)) = (loser.mission, loser.next_waypoint) else {
+            return;
+        };
+        // Keep clear of every robot, and of the whole way ahead of those blocking it
+        let mut grid = Grid::new(self.control.map(), loser.reach);
+        for other in claims.iter().filter(|c| c.id != loser.id) {
+            let clearance = other.reach + SAFE_DISTANCE;
+            let path = match guard.get(&other.id).and_then(|r| r.mission.as_ref()) {
+                Some(m) if waits.contains(&other.id) && m.is_open() => path_ahead(other.position, &m.remaining(), f32::MAX),
+                _ => other.ahead.clone(),
+            };
+            grid.block(other.position, clearance);
+            for point in path {
+                grid.block(point, clearance);
+            }
+        }
+
+        let waypoints = match grid.plan(loser.position, next) {
+            // The route already ends on `next`
+            Ok(path) if path.len() > 1 => path[..path.len() - 1].to_vec(),
+            _ => match grid.escape(loser.position) {
+                // Stepping less than a cell aside makes no room
+                Some(aside) if (aside.0 - loser.position.0).hypot(aside.1 - loser.position.1) > CELL => vec![aside],
+                _ => {
+                    let _ = self.tx_log.send(format!("TRAFFIC: {}; no way round for {}", why, loser.id));
+                    self.yielding.insert(loser.id.clone(), Instant::now());
+                    return;
+                }
+            },
+        };
+
+        if let Some(robot) = guard.get_mut(&loser.id) {
+            let _ = robot.tx_to_client.send(ServerMessage::Detour { mission, waypoints: waypoints.clone() });
+            let _ = self.tx_log.send(format!("TRAFFIC: {}; {} detours via {} waypoint(s)", why, loser.id, waypoints.len()));
+            if let Some(m) = robot.mission.as_mut() {
+                m.detour = waypoints;
+            }
+        }
+        self.yielding.insert(loser.id.clone(), Instant::now());
+    }
+}
+
+// Points every half cell along the path from `from` through the waypoints,
+// for at most `length`
+fn path_ahead(from: (f32, f32), waypoints: &[(f32, f32)], length: f32) -> Vec<(f32, f32)> {
+    let mut points = vec![from];
+    let (mut at, mut left) = (from, length);
+    for &to in waypoints {
+        let d = (to.0 - at.0).hypot(to.1 - at.1);
+        if d == 0.0 {
+            continue;
+        }
+        let along = |t: f32| (at.0 + (to.0 - at.0) * t / d, at.1 + (to.1 - at.1) * t / d);
+        let mut t = CELL / 2.0;
+        while t < d.min(left) {
+            points.push(along(t));
+            t += CELL / 2.0;
+        }
+        if d >= left {
+            points.push(along(left));
+            return points;
+        }
+        points.push(to);
+        (at, left) = (to, left - d);
+    }
+    points
+}
+
+// Cells of the workspace a disc touches
+fn cells_around((x, y): (f32, f32), radius: f32) -> Vec<Cell> {
+    let span = |v: f32, size: f32| {
+        let last = (size / CELL).ceil() as i32 - 1;
+        ((v - radius) / CELL).floor().max(0.0) as i32..=(((v + radius) / CELL).floor() as i32).min(last)
+    };
+    span(x, BOUNDARY_WIDTH)
+        .flat_map(|c| span(y, BOUNDARY_HEIGHT).map(move |r| (c, r)))
+        .filter(|&(c, r)| {
+            // Nearest point of the cell to the centre
+            let nx = x.clamp(c as f32 * CELL, (c + 1) as f32 * CELL);
+            let ny = y.clamp(r as f32 * CELL, (r + 1) as f32 * CELL);
+            (nx - x).hypot(ny - y) <= radius
+        })
+        .collect()
+}
+
+// A chain of held robots from `start` back to itself, if there is one
+fn find_cycle(held: &BTreeMap<String, Vec<String>>, start: &str) -> Option<Vec<String>> {
+    fn walk(
+        held: &BTreeMap<String, Vec<String>>,
+        node: &str,
+        start: &str,
+        path: &mut Vec<String>,
+        seen: &mut HashSet<String>,
+    ) -> bool {
+        for next in held.get(node).into_iter().flatten() {
+            if next == start {
+                return true;
+            }
+            if seen.insert(next.clone()) {
+                path.push(next.clone());
+                if walk(held, next, start, path, seen) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+    let mut path = vec![start.to_string()];
+    walk(held, start, start, &mut path, &mut HashSet::new()).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(edges: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        edges.iter().map(|(from, to)| (from.to_string(), to.iter().map(|t| t.to_string()).collect())).collect()
+    }
+
+    #[test]
+    fn two_robots_holding_each_other_deadlock() {
+        let held = held(&[("A", &["B"]), ("B", &["A"])]);
+        assert_eq!(find_cycle(&held, "A"), Some(vec!["A".to_string(), "B".to_string()]));
+        assert_eq!(find_cycle(&held, "B"), Some(vec!["B".to_string(), "A".to_string()]));
+    }
+
+    #[test]
+    fn finds_longer_cycles_past_dead_ends() {
+        let held = held(&[("A", &["D", "B"]), ("B", &["C"]), ("C", &["A"]), ("D", &["E"])]);
+        assert_eq!(find_cycle(&held, "A"), Some(vec!["A".into(), "B".into(), "C".into()]));
+    }
+
+    #[test]
+    fn a_chain_of_holds_is_not_a_deadlock() {
+        let held = held(&[("A", &["B"]), ("B", &["C"])]);
+        assert_eq!(find_cycle(&held, "A"), None);
+        assert_eq!(find_cycle(&held, "C"), None);
+    }
+
+    #[test]
+    fn only_cycles_through_the_start_count() {
+        // B and C wait on each other, but A is merely queued behind them
+        let held = held(&[("A", &["B"]), ("B", &["C"]), ("C", &["B"])]);
+        assert_eq!(find_cycle(&held, "A"), None);
+        assert!(find_cycle(&held, "B").is_some());
+    }
+
+    #[test]
+    fn path_ahead_stops_after_length() {
+        let points = path_ahead((0.0, 0.0), &[(100.0, 0.0)], LOOKAHEAD);
+        assert_eq!(points.first(), Some(&(0.0, 0.0)));
+        assert_eq!(points.last(), Some(&(LOOKAHEAD, 0.0)));
+        // Turns the corner with whatever length is left
+        let points = path_ahead((0.0, 0.0), &[(30.0, 0.0), (30.0, 100.0)], LOOKAHEAD);
+        assert_eq!(points.last(), Some(&(30.0, 10.0)));
+    }
+
+    #[test]
+    fn cells_around_covers_the_disc_only() {
+        let cells = cells_around((CELL / 2.0, CELL / 2.0), 1.0);
+        assert_eq!(cells, vec![(0, 0)]);
+        let cells = cells_around((CELL, CELL), 5.0);
+        assert_eq!(cells.len(), 4);
+        // The corners of the neighbouring cells are out of reach
+        assert!(!cells_around((CELL / 2.0, CELL / 2.0), CELL * 0.6).contains(&(1, 1)));
+    }
+
+    #[test]
+    fn cells_around_stays_inside_the_workspace() {
+        let cells = cells_around((300.0, 200.0), 1e9);
+        let (cols, rows) = ((BOUNDARY_WIDTH / CELL) as usize, (BOUNDARY_HEIGHT / CELL) as usize);
+        assert_eq!(cells.len(), cols * rows);
+        assert!(cells_around((0.0, 0.0), 5.0).iter().all(|&(c, r)| c >= 0 && r >= 0));
+    }
+}
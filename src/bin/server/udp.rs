@@ -0,0 +1,73 @@
+// UDP telemetry receiver. Robots that ask for it in their Hello get a session
+// token; their RobotState updates then arrive as datagrams while safety
+// commands stay on the reliable TCP connection.
+use crate::SharedRobots;
+use assignment2::codec::Codec;
+use assignment2::{datagram, UdpSession};
+use std::collections::HashMap;
+use std::net::{IpAddr, UdpSocket};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+struct Peer {
+    robot_id: String,
+    codec: Codec,
+    // Datagrams must come from the same host as the TCP connection
+    ip: IpAddr,
+}
+
+#[derive(Clone)]
+pub struct UdpHub {
+    port: u16,
+    peers: Arc<Mutex<HashMap<u64, Peer>>>,
+}
+
+impl UdpHub {
+    pub fn spawn(addr: &str, robots: SharedRobots, max_frame: usize, tx_log: mpsc::Sender<String>) -> Option<Self> {
+        let socket = match UdpSocket::bind(addr) {
+            Ok(s) => s,
+            Err(e) => {
+                let _ = tx_log.send(format!("UDP telemetry disabled, cannot bind {}: {}", addr, e));
+                return None;
+            }
+        };
+        let port = socket.local_addr().ok()?.port();
+        let hub = Self { port, peers: Arc::new(Mutex::new(HashMap::new())) };
+        let _ = tx_log.send(format!("UDP telemetry listening on {}", addr));
+
+        let peers = hub.peers.clone();
+        thread::spawn(move || {
+            let mut buf = vec![0u8; max_frame + 8];
+            loop {
+                let Ok((len, from)) = socket.recv_from(&mut buf) else { continue };
+                let Some((token, payload)) = datagram::split(&buf[..len]) else { continue };
+
+                let (robot_id, codec) = match peers.lock().unwrap().get(&token) {
+                    Some(peer) if peer.ip == from.ip() => (peer.robot_id.clone(), peer.codec),
+                    _ => continue, // Unknown session or spoofed source
+                };
+                let Ok(state) = datagram::decode(codec, payload) else { continue };
+                if state.id != robot_id {
+                    continue;
+                }
+
+                // Robots register over TCP; datagrams before that are dropped
+                if let Some(robot) = robots.lock().unwrap().get_mut(&robot_id) {
+                    robot.update(state);
+                }
+            }
+        });
+
+        Some(hub)
+    }
+
+    pub fn register(&self, robot_id: &str, codec: Codec, ip: IpAddr) -> UdpSession {
+        let token = rand::random::<u64>();
+        self.peers.lock().unwrap().insert(token, Peer { robot_id: robot_id.to_string(), codec, ip });
+        UdpSession { port: self.port, token }
+    }
+
+    pub fn unregister(&self, session: &UdpSession) {
+        self.peers.lock().unwrap().remove(&session.token);
+    }
+}
@@ -0,0 +1,35 @@
+// Minimal command-line parsing shared by the binaries.
+// Options are `--name value` pairs; switches are a bare `--name`.
+use std::str::FromStr;
+
+pub struct Args {
+    args: Vec<String>,
+}
+
+impl Args {
+    pub fn from_env() -> Self {
+        Self { args: std::env::args().skip(1).collect() }
+    }
+
+    // Value following `--name`, if present
+    pub fn value(&self, name: &str) -> Option<&str> {
+        let pos = self.args.iter().position(|a| a == name)?;
+        self.args.get(pos + 1).map(|s| s.as_str())
+    }
+
+    // True if the bare switch `--name` was given
+    pub fn flag(&self, name: &str) -> bool {
+        self.args.iter().any(|a| a == name)
+    }
+
+    // Parsed value of `--name`, falling back to `default` when missing or malformed
+    pub fn parse_or<T: FromStr>(&self, name: &str, default: T) -> T {
+        match self.value(name) {
+            Some(raw) => raw.parse().unwrap_or_else(|_| {
+                eprintln!("Ignoring invalid value for {}: {}", name, raw);
+                default
+            }),
+            None => default,
+        }
+    }
+}
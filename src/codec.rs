@@ -0,0 +1,203 @@
+// Framing and wire codecs for ClientMessage / ServerMessage.
+//
+// Every message travels in a frame: a 4-byte big-endian payload length
+// followed by the payload. Frames larger than the reader's limit close the
+// connection, so a misbehaving peer cannot make us buffer without bound.
+//
+// Payload codecs:
+//   Json:        serde_json (human readable, the debug default)
+//   MessagePack: compact binary
+//
+// The codec is negotiated in the handshake: Hello and Welcome/Rejected are
+// always JSON, everything after uses the codec named in the Hello.
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+pub const DEFAULT_MAX_FRAME: usize = 64 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+#[derive(Debug)]
+pub enum CodecError {
+    // Connection is unusable (closed mid-frame, I/O failure)
+    Io(io::Error),
+    // Peer announced a frame above the limit; the connection must be closed
+    FrameTooLarge { len: usize, max: usize },
+    // One message could not be decoded; the stream is still in sync
+    Decode(String),
+}
+
+impl CodecError {
+    // True if the connection cannot continue after this error
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, CodecError::Decode(_))
+    }
+}
+
+impl std::fmt::Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CodecError::Io(e) => write!(f, "I/O error: {}", e),
+            CodecError::FrameTooLarge { len, max } => {
+                write!(f, "Frame of {} bytes exceeds the {} byte limit", len, max)
+            }
+            CodecError::Decode(e) => write!(f, "Decode error: {}", e),
+        }
+    }
+}
+
+impl std::str::FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "json" => Ok(Codec::Json),
+            "msgpack" | "message_pack" => Ok(Codec::MessagePack),
+            _ => Err(format!("Unknown codec '{}' (expected json or msgpack)", s)),
+        }
+    }
+}
+
+impl Codec {
+    pub fn encode<T: Serialize>(&self, msg: &T) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::Json => serde_json::to_vec(msg).map_err(io::Error::other),
+            // Named fields: the adjacently tagged enums need a self-describing encoding
+            Codec::MessagePack => rmp_serde::to_vec_named(msg).map_err(io::Error::other),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, payload: &[u8]) -> Result<T, CodecError> {
+        match self {
+            Codec::Json => serde_json::from_slice(payload).map_err(|e| CodecError::Decode(e.to_string())),
+            Codec::MessagePack => rmp_serde::from_slice(payload).map_err(|e| CodecError::Decode(e.to_string())),
+        }
+    }
+
+    pub fn write<T: Serialize>(&self, w: &mut impl Write, msg: &T) -> io::Result<()> {
+        write_frame(w, &self.encode(msg)?)
+    }
+
+    // Ok(None) means the peer closed the connection cleanly
+    pub fn read<T: DeserializeOwned>(&self, r: &mut impl Read, max_frame: usize) -> Result<Option<T>, CodecError> {
+        match read_frame(r, max_frame)? {
+            Some(payload) => self.decode(&payload).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+pub fn write_frame(w: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len()).map_err(|_| io::Error::other("Frame too large to send"))?;
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(payload);
+    w.write_all(&frame)?;
+    w.flush()
+}
+
+// Ok(None) on a clean close between frames
+pub fn read_frame(r: &mut impl Read, max_frame: usize) -> Result<Option<Vec<u8>>, CodecError> {
+    let mut header = [0u8; 4];
+    let mut filled = 0;
+    while filled < header.len() {
+        match r.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            // Closed part way through a header: not a clean close
+            Ok(0) => return Err(CodecError::Io(io::ErrorKind::UnexpectedEof.into())),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(CodecError::Io(e)),
+        }
+    }
+
+    let len = u32::from_be_bytes(header) as usize;
+    if len > max_frame {
+        return Err(CodecError::FrameTooLarge { len, max: max_frame });
+    }
+    let mut payload = vec![0; len];
+    r.read_exact(&mut payload).map_err(CodecError::Io)?;
+    Ok(Some(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClientMessage;
+    use std::io::Cursor;
+
+    fn framed(payloads: &[&[u8]]) -> Cursor<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for payload in payloads {
+            write_frame(&mut bytes, payload).unwrap();
+        }
+        Cursor::new(bytes)
+    }
+
+    #[test]
+    fn messages_round_trip_in_both_codecs() {
+        for codec in [Codec::Json, Codec::MessagePack] {
+            let mut bytes = Vec::new();
+            codec.write(&mut bytes, &ClientMessage::Disconnect("A".into())).unwrap();
+            let mut reader = Cursor::new(bytes);
+            let msg = codec.read::<ClientMessage>(&mut reader, DEFAULT_MAX_FRAME).unwrap();
+            assert!(matches!(msg, Some(ClientMessage::Disconnect(id)) if id == "A"));
+            assert!(codec.read::<ClientMessage>(&mut reader, DEFAULT_MAX_FRAME).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn frames_up_to_the_limit_are_accepted() {
+        let payload = vec![7u8; 16];
+        let mut reader = framed(&[&payload, &[]]);
+        assert_eq!(read_frame(&mut reader, 16).unwrap(), Some(payload));
+        assert_eq!(read_frame(&mut reader, 16).unwrap(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn oversized_frames_are_refused_before_reading_them() {
+        let mut reader = framed(&[&[0u8; 17]]);
+        assert!(matches!(read_frame(&mut reader, 16), Err(CodecError::FrameTooLarge { len: 17, max: 16 })));
+        // Only the header was consumed
+        assert_eq!(reader.position(), 4);
+
+        // A header announcing gigabytes is refused without allocating them
+        let mut reader = Cursor::new(u32::MAX.to_be_bytes().to_vec());
+        let err = read_frame(&mut reader, DEFAULT_MAX_FRAME).unwrap_err();
+        assert!(err.is_fatal());
+    }
+
+    #[test]
+    fn clean_close_only_between_frames() {
+        assert_eq!(read_frame(&mut Cursor::new(Vec::new()), 16).unwrap(), None);
+        for cut in 1..4 {
+            let mut reader = Cursor::new(vec![0u8; cut]);
+            assert!(matches!(read_frame(&mut reader, 16), Err(CodecError::Io(_))), "header cut after {}", cut);
+        }
+    }
+
+    #[test]
+    fn truncated_payload_is_an_io_error() {
+        let mut bytes = framed(&[b"hello"]).into_inner();
+        bytes.truncate(bytes.len() - 2);
+        assert!(matches!(read_frame(&mut Cursor::new(bytes), 16), Err(CodecError::Io(_))));
+    }
+
+    #[test]
+    fn undecodable_message_keeps_the_stream_in_sync() {
+        let mut bytes = framed(&[b"not json"]).into_inner();
+        Codec::Json.write(&mut bytes, &ClientMessage::Disconnect("B".into())).unwrap();
+        let mut reader = Cursor::new(bytes);
+        let err = Codec::Json.read::<ClientMessage>(&mut reader, DEFAULT_MAX_FRAME).unwrap_err();
+        assert!(!err.is_fatal());
+        let next = Codec::Json.read::<ClientMessage>(&mut reader, DEFAULT_MAX_FRAME).unwrap();
+        assert!(matches!(next, Some(ClientMessage::Disconnect(id)) if id == "B"));
+    }
+}
@@ -0,0 +1,85 @@
+// UDP telemetry datagrams: an 8-byte big-endian session token followed by
+// a RobotState in the codec negotiated on the TCP connection. Latest value
+// wins: the server drops anything older than the last `seq` it accepted.
+use crate::codec::{Codec, CodecError};
+use crate::{RobotState, UdpSession};
+use std::io;
+use std::net::{IpAddr, UdpSocket};
+
+pub fn encode(token: u64, codec: Codec, state: &RobotState) -> io::Result<Vec<u8>> {
+    let mut datagram = token.to_be_bytes().to_vec();
+    datagram.extend_from_slice(&codec.encode(state)?);
+    Ok(datagram)
+}
+
+// Session token and undecoded payload, or None if the datagram is too short
+pub fn split(datagram: &[u8]) -> Option<(u64, &[u8])> {
+    let (token, payload) = datagram.split_first_chunk::<8>()?;
+    Some((u64::from_be_bytes(*token), payload))
+}
+
+pub fn decode(codec: Codec, payload: &[u8]) -> Result<RobotState, CodecError> {
+    codec.decode(payload)
+}
+
+// Client side of a UDP telemetry session
+pub struct UdpLink {
+    socket: UdpSocket,
+    session: UdpSession,
+    codec: Codec,
+}
+
+impl UdpLink {
+    pub fn open(server_ip: IpAddr, session: UdpSession, codec: Codec) -> io::Result<Self> {
+        let bind = if server_ip.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(bind)?;
+        socket.connect((server_ip, session.port))?;
+        Ok(Self { socket, session, codec })
+    }
+
+    pub fn send(&self, state: &RobotState) -> io::Result<()> {
+        self.socket.send(&encode(self.session.token, self.codec, state)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(seq: u64) -> RobotState {
+        RobotState {
+            id: "A".into(),
+            x: 10.0,
+            y: 20.0,
+            speed: 0.0,
+            angle: 0.0,
+            active: true,
+            color: (0, 0, 0),
+            seq,
+            sent_at_ms: 0,
+            stopping_distance: 0.0,
+            model: Default::default(),
+        }
+    }
+
+    #[test]
+    fn datagrams_round_trip_in_both_codecs() {
+        for codec in [Codec::Json, Codec::MessagePack] {
+            let datagram = encode(0xDEAD_BEEF_0000_0001, codec, &state(42)).unwrap();
+            let (token, payload) = split(&datagram).unwrap();
+            assert_eq!(token, 0xDEAD_BEEF_0000_0001);
+            let decoded = decode(codec, payload).unwrap();
+            assert_eq!((decoded.id.as_str(), decoded.x, decoded.y, decoded.seq), ("A", 10.0, 20.0, 42));
+        }
+    }
+
+    #[test]
+    fn short_or_garbled_datagrams_are_refused() {
+        assert!(split(&[1, 2, 3]).is_none());
+        let (_, payload) = split(&[0; 8]).unwrap();
+        assert!(payload.is_empty());
+        assert!(decode(Codec::Json, payload).is_err());
+        assert!(decode(Codec::MessagePack, b"\xc1garbage").is_err());
+    }
+}
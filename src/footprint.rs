@@ -0,0 +1,357 @@
+// Robot footprints: the outline a robot occupies, declared in its Hello.
+//
+// Shapes are given in the robot frame: x forward along the body heading,
+// y to the right (screen coordinates, like the workspace). `place` turns one
+// into an outline in the workspace, and `clearance` is the gap between two
+// outlines, which is what the server's safety checks compare.
+use crate::{BOUNDARY_HEIGHT, BOUNDARY_WIDTH};
+use serde::{Deserialize, Serialize};
+
+// Corners allowed in a polygon; every safety tick compares outlines pairwise
+pub const MAX_POINTS: usize = 32;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Footprint {
+    Circle { radius: f32 },
+    // Centred on the robot's position
+    Rectangle { length: f32, width: f32 },
+    // Corners in order, in the robot frame
+    Polygon { points: Vec<(f32, f32)> },
+}
+
+// Same size as the 10 px circle robots were always drawn as
+impl Default for Footprint {
+    fn default() -> Self {
+        Footprint::Circle { radius: 10.0 }
+    }
+}
+
+// Command-line form: `circle:10`, `rect:60x30` (length x width) or
+// `poly:20,0;-10,10;-10,-10`
+impl std::str::FromStr for Footprint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid footprint '{}' (expected circle:R, rect:LxW or poly:x,y;x,y;...)", s);
+        let number = |v: &str| v.trim().parse::<f32>().ok().filter(|n| n.is_finite());
+        let footprint = match s.split_once(':').ok_or_else(invalid)? {
+            ("circle", r) => Footprint::Circle { radius: number(r).ok_or_else(invalid)? },
+            ("rect", size) => {
+                let (l, w) = size.split_once('x').ok_or_else(invalid)?;
+                Footprint::Rectangle { length: number(l).ok_or_else(invalid)?, width: number(w).ok_or_else(invalid)? }
+            }
+            ("poly", corners) => Footprint::Polygon {
+                points: corners
+                    .split(';')
+                    .map(|p| p.split_once(',').and_then(|(x, y)| Some((number(x)?, number(y)?))))
+                    .collect::<Option<_>>()
+                    .ok_or_else(invalid)?,
+            },
+            _ => return Err(invalid()),
+        };
+        footprint.validate()?;
+        Ok(footprint)
+    }
+}
+
+impl Footprint {
+    // Rejects shapes with no area, too many corners or that do not fit in the
+    // workspace, from the command line or a Hello
+    pub fn validate(&self) -> Result<(), String> {
+        let ok = match self {
+            Footprint::Circle { radius } => *radius > 0.0,
+            Footprint::Rectangle { length, width } => *length > 0.0 && *width > 0.0,
+            Footprint::Polygon { points } => {
+                points.len() >= 3
+                    && points.iter().all(|(x, y)| x.is_finite() && y.is_finite())
+                    && signed_area(points).abs() > 0.0
+            }
+        };
+        if !ok {
+            return Err(format!("Footprint {:?} has no area", self));
+        }
+        if let Footprint::Polygon { points } = self
+            && points.len() > MAX_POINTS
+        {
+            return Err(format!("Footprint has {} corners (at most {})", points.len(), MAX_POINTS));
+        }
+        let max_reach = BOUNDARY_WIDTH.min(BOUNDARY_HEIGHT) / 2.0;
+        if self.reach() > max_reach {
+            return Err(format!("Footprint reaches {:.0} px from its centre (at most {:.0})", self.reach(), max_reach));
+        }
+        Ok(())
+    }
+
+    // Distance from the robot's position to the furthest point of the outline
+    pub fn reach(&self) -> f32 {
+        match self {
+            Footprint::Circle { radius } => *radius,
+            Footprint::Rectangle { length, width } => length.hypot(*width) / 2.0,
+            Footprint::Polygon { points } => points.iter().map(|(x, y)| x.hypot(*y)).fold(0.0, f32::max),
+        }
+    }
+
+    // Outline in the workspace for a robot at (x, y) facing `heading`
+    pub fn place(&self, x: f32, y: f32, heading: f32) -> Placed {
+        let (sin, cos) = heading.sin_cos();
+        let to_world = |&(px, py): &(f32, f32)| (x + px * cos - py * sin, y + px * sin + py * cos);
+        match self {
+            Footprint::Circle { radius } => Placed::Circle { centre: (x, y), radius: *radius },
+            Footprint::Rectangle { length, width } => {
+                let (l, w) = (length / 2.0, width / 2.0);
+                Placed::Polygon([(l, w), (-l, w), (-l, -w), (l, -w)].iter().map(to_world).collect())
+            }
+            Footprint::Polygon { points } => Placed::Polygon(points.iter().map(to_world).collect()),
+        }
+    }
+}
+
+// A footprint placed in the workspace
+#[derive(Clone, Debug)]
+pub enum Placed {
+    Circle { centre: (f32, f32), radius: f32 },
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl Placed {
+    // Gap between the two outlines; 0 when they touch or overlap
+    pub fn clearance(&self, other: &Placed) -> f32 {
+        match (self, other) {
+            (Placed::Circle { centre: a, radius: ra }, Placed::Circle { centre: b, radius: rb }) => {
+                ((a.0 - b.0).hypot(a.1 - b.1) - ra - rb).max(0.0)
+            }
+            (Placed::Circle { centre, radius }, Placed::Polygon(poly))
+            | (Placed::Polygon(poly), Placed::Circle { centre, radius }) => {
+                if contains(poly, *centre) {
+                    return 0.0;
+                }
+                (edges(poly).map(|(p, q)| point_segment(*centre, p, q)).fold(f32::MAX, f32::min) - radius).max(0.0)
+            }
+            (Placed::Polygon(a), Placed::Polygon(b)) => {
+                let crossing = edges(a).any(|(p, q)| edges(b).any(|(r, s)| segments_cross(p, q, r, s)));
+                if crossing || a.first().is_some_and(|&p| contains(b, p)) || b.first().is_some_and(|&p| contains(a, p)) {
+                    return 0.0;
+                }
+                // Apart, so the closest points include a corner of one outline
+                let a_to_b = a.iter().flat_map(|&p| edges(b).map(move |(r, s)| point_segment(p, r, s)));
+                let b_to_a = b.iter().flat_map(|&p| edges(a).map(move |(r, s)| point_segment(p, r, s)));
+                a_to_b.chain(b_to_a).fold(f32::MAX, f32::min)
+            }
+        }
+    }
+
+    // (min x, min y, max x, max y)
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        match self {
+            Placed::Circle { centre: (x, y), radius } => (x - radius, y - radius, x + radius, y + radius),
+            Placed::Polygon(points) => points.iter().fold(
+                (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+                |(x0, y0, x1, y1), &(x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            ),
+        }
+    }
+}
+
+// Edges of a closed polygon, last corner back to the first
+fn edges(poly: &[(f32, f32)]) -> impl Iterator<Item = ((f32, f32), (f32, f32))> + '_ {
+    poly.iter().copied().zip(poly.iter().copied().cycle().skip(1))
+}
+
+fn point_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len2).clamp(0.0, 1.0) } else { 0.0 };
+    (a.0 + t * dx - p.0).hypot(a.1 + t * dy - p.1)
+}
+
+fn segments_cross(p: (f32, f32), q: (f32, f32), r: (f32, f32), s: (f32, f32)) -> bool {
+    let (d1, d2) = (cross(r, s, p), cross(r, s, q));
+    let (d3, d4) = (cross(p, q, r), cross(p, q, s));
+    d1 * d2 <= 0.0 && d3 * d4 <= 0.0 && (d1 != d2 || d3 != d4)
+}
+
+// Shoelace formula; positive when the corners turn the way +x turns to +y
+fn signed_area(poly: &[(f32, f32)]) -> f32 {
+    edges(poly).map(|((x1, y1), (x2, y2))| x1 * y2 - x2 * y1).sum::<f32>() / 2.0
+}
+
+fn cross(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+// Triangles covering an outline, as corner indices, so that concave outlines
+// can be filled. Ear clipping: cut off corners whose triangle holds no other
+// corner until three are left. A self-crossing outline may run out of ears;
+// the rest is then fanned out as if convex.
+pub fn triangulate(poly: &[(f32, f32)]) -> Vec<[usize; 3]> {
+    let turn = signed_area(poly).signum();
+    let mut left: Vec<usize> = (0..poly.len()).collect();
+    let mut triangles = Vec::new();
+    while left.len() > 3 {
+        let n = left.len();
+        let corners = |i: usize| (left[(i + n - 1) % n], left[i], left[(i + 1) % n]);
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = corners(i);
+            let (pa, pb, pc) = (poly[a], poly[b], poly[c]);
+            cross(pa, pb, pc) * turn > 0.0
+                && !left.iter().any(|&j| {
+                    let d = [cross(pa, pb, poly[j]), cross(pb, pc, poly[j]), cross(pc, pa, poly[j])];
+                    let inside = d.iter().all(|&v| v >= 0.0) || d.iter().all(|&v| v <= 0.0);
+                    inside && ![a, b, c].contains(&j)
+                })
+        });
+        let Some(i) = ear else { break };
+        let (a, b, c) = corners(i);
+        triangles.push([a, b, c]);
+        left.remove(i);
+    }
+    triangles.extend((1..left.len().saturating_sub(1)).map(|k| [left[0], left[k], left[k + 1]]));
+    triangles
+}
+
+// Even-odd rule, so concave outlines work too
+fn contains(poly: &[(f32, f32)], (x, y): (f32, f32)) -> bool {
+    edges(poly).fold(false, |inside, ((x1, y1), (x2, y2))| {
+        if (y1 > y) != (y2 > y) && x < x1 + (y - y1) * (x2 - x1) / (y2 - y1) { !inside } else { inside }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{} != {}", actual, expected);
+    }
+
+    fn square(x: f32, y: f32, side: f32) -> Placed {
+        Footprint::Rectangle { length: side, width: side }.place(x, y, 0.0)
+    }
+
+    #[test]
+    fn parses_the_command_line_forms() {
+        assert_eq!("circle:10".parse(), Ok(Footprint::Circle { radius: 10.0 }));
+        assert_eq!("rect:60x30".parse(), Ok(Footprint::Rectangle { length: 60.0, width: 30.0 }));
+        assert_eq!(
+            "poly:20,0;-10,10;-10,-10".parse(),
+            Ok(Footprint::Polygon { points: vec![(20.0, 0.0), (-10.0, 10.0), (-10.0, -10.0)] })
+        );
+    }
+
+    #[test]
+    fn rejects_shapes_with_no_area_or_bad_syntax() {
+        for bad in [
+            "circle:0",
+            "circle:-5",
+            "rect:10x0",
+            "poly:0,0;10,10",
+            "poly:0,0;10,0;20,0",
+            "poly:",
+            "rect:10",
+            "square:3",
+            "circle:nan",
+        ] {
+            assert!(bad.parse::<Footprint>().is_err(), "{} was accepted", bad);
+        }
+    }
+
+    #[test]
+    fn gap_between_circles() {
+        let a = Footprint::Circle { radius: 10.0 }.place(0.0, 0.0, 0.0);
+        let b = Footprint::Circle { radius: 5.0 }.place(50.0, 0.0, 1.0);
+        assert_near(a.clearance(&b), 35.0);
+        assert_near(b.clearance(&a), 35.0);
+    }
+
+    #[test]
+    fn gap_between_circle_and_polygon() {
+        let circle = Footprint::Circle { radius: 5.0 }.place(30.0, 0.0, 0.0);
+        let square = square(0.0, 0.0, 20.0);
+        assert_near(square.clearance(&circle), 15.0);
+        assert_near(circle.clearance(&square), 15.0);
+        // Off a corner, the gap is to the corner
+        let diagonal = Footprint::Circle { radius: 5.0 }.place(20.0, 20.0, 0.0);
+        assert_near(square.clearance(&diagonal), 200f32.sqrt() - 5.0);
+    }
+
+    #[test]
+    fn gap_between_polygons() {
+        assert_near(square(0.0, 0.0, 20.0).clearance(&square(50.0, 0.0, 20.0)), 30.0);
+        let triangle = Footprint::Polygon { points: vec![(0.0, 0.0), (10.0, 10.0), (0.0, 10.0)] }.place(40.0, -5.0, 0.0);
+        assert_near(square(0.0, 0.0, 20.0).clearance(&triangle), 30.0);
+    }
+
+    #[test]
+    fn overlapping_shapes_have_no_gap() {
+        let circle = |x, r| Footprint::Circle { radius: r }.place(x, 0.0, 0.0);
+        assert_eq!(circle(0.0, 10.0).clearance(&circle(15.0, 10.0)), 0.0);
+        // Circle centre inside the square, and a circle only crossing its edge
+        assert_eq!(square(0.0, 0.0, 20.0).clearance(&circle(0.0, 2.0)), 0.0);
+        assert_eq!(square(0.0, 0.0, 20.0).clearance(&circle(14.0, 5.0)), 0.0);
+        // Edges crossing, and one square wholly inside the other
+        assert_eq!(square(0.0, 0.0, 20.0).clearance(&square(15.0, 15.0, 20.0)), 0.0);
+        assert_eq!(square(0.0, 0.0, 40.0).clearance(&square(0.0, 0.0, 10.0)), 0.0);
+        assert_eq!(square(0.0, 0.0, 10.0).clearance(&square(0.0, 0.0, 40.0)), 0.0);
+    }
+
+    #[test]
+    fn rotated_rectangle() {
+        // 60 long and 20 wide, turned to face down the screen
+        let rect = Footprint::Rectangle { length: 60.0, width: 20.0 }.place(100.0, 100.0, FRAC_PI_2);
+        let (x0, y0, x1, y1) = rect.bounds();
+        assert_near(x0, 90.0);
+        assert_near(y0, 70.0);
+        assert_near(x1, 110.0);
+        assert_near(y1, 130.0);
+        let probe = |x, y| Footprint::Circle { radius: 5.0 }.place(x, y, 0.0);
+        assert_near(rect.clearance(&probe(100.0, 150.0)), 15.0);
+        assert_near(rect.clearance(&probe(120.0, 100.0)), 5.0);
+        assert_eq!(rect.clearance(&probe(100.0, 125.0)), 0.0);
+    }
+
+    #[test]
+    fn contains_follows_concave_outlines() {
+        // An L shape, with the notch at the top right
+        let l = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (20.0, 10.0), (20.0, 20.0), (0.0, 20.0)];
+        assert!(contains(&l, (5.0, 5.0)));
+        assert!(contains(&l, (15.0, 15.0)));
+        assert!(!contains(&l, (15.0, 5.0)));
+        assert!(!contains(&l, (25.0, 15.0)));
+    }
+
+    #[test]
+    fn oversized_footprints_are_rejected() {
+        assert!("circle:1e9".parse::<Footprint>().unwrap_err().contains("reaches"));
+        assert!("rect:1000x10".parse::<Footprint>().is_err());
+        assert!("circle:200".parse::<Footprint>().is_ok());
+        let many = Footprint::Polygon { points: (0..MAX_POINTS + 1).map(|i| ((i as f32).cos(), (i as f32).sin())).collect() };
+        assert!(many.validate().unwrap_err().contains("corners"));
+        let nan = Footprint::Polygon { points: vec![(0.0, 0.0), (f32::NAN, 1.0), (1.0, 0.0)] };
+        assert!(nan.validate().is_err());
+    }
+
+    #[test]
+    fn triangulates_concave_outlines() {
+        let area = |poly: &[(f32, f32)], triangles: &[[usize; 3]]| {
+            triangles.iter().map(|&[a, b, c]| cross(poly[a], poly[b], poly[c]).abs() / 2.0).sum::<f32>()
+        };
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let triangles = triangulate(&square);
+        assert_eq!(triangles.len(), 2);
+        assert_near(area(&square, &triangles), 100.0);
+
+        // The L shape from above, and the same corners the other way round
+        let mut l = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (20.0, 10.0), (20.0, 20.0), (0.0, 20.0)];
+        for _ in 0..2 {
+            let triangles = triangulate(&l);
+            assert_eq!(triangles.len(), 4);
+            assert_near(area(&l, &triangles), 300.0);
+            // No triangle covers the notch
+            let notch = (15.0, 5.0);
+            assert!(triangles.iter().all(|&[a, b, c]| !contains(&[l[a], l[b], l[c]], notch)));
+            l.reverse();
+        }
+    }
+}
@@ -4,6 +4,20 @@
 // EEN1097 Assignment 2 - Shared Types
 use serde::{Deserialize, Serialize};
 
+pub mod cli;
+pub mod codec;
+pub mod datagram;
+pub mod footprint;
+pub mod model;
+pub mod net;
+pub mod sim;
+pub mod transport;
+
+use codec::Codec;
+use footprint::Footprint;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 pub const BOUNDARY_WIDTH: f32 = 600.0;
 pub const BOUNDARY_HEIGHT: f32 = 400.0;
 
@@ -18,27 +32,253 @@ pub struct RobotState {
     pub active: bool,
     // Visual flair: each robot can have a color
     pub color: (u8, u8, u8), 
+    // Increases by one with every telemetry frame; 0 means unsequenced
+    #[serde(default)]
+    pub seq: u64,
+    // Robot clock (ms since the Unix epoch) when the frame was sent
+    #[serde(default)]
+    pub sent_at_ms: u64,
+    // Distance the robot needs to brake to rest from its current speed
+    #[serde(default)]
+    pub stopping_distance: f32,
+    // Drive model and its own readings; `speed` and `angle` are always the
+    // direction of travel whatever the model
+    #[serde(default)]
+    pub model: ModelState,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelState {
+    #[default]
+    Unicycle,
+    // Wheel rim speeds in px/s
+    DifferentialDrive { left: f32, right: f32, track_width: f32 },
+    // Velocity in workspace coordinates, and the way the body faces (radians)
+    Holonomic { vx: f32, vy: f32, heading: f32 },
+}
+
+impl RobotState {
+    // Way the body faces; only a holonomic robot can face away from its travel
+    pub fn body_heading(&self) -> f32 {
+        match self.model {
+            ModelState::Holonomic { heading, .. } => heading,
+            _ => self.angle,
+        }
+    }
+}
+
+impl ModelState {
+    // e.g. "diff drive (L 40, R 60 px/s)"
+    pub fn describe(&self) -> String {
+        match self {
+            ModelState::Unicycle => "unicycle".to_string(),
+            ModelState::DifferentialDrive { left, right, .. } => {
+                format!("diff drive (L {:.0}, R {:.0} px/s)", left, right)
+            }
+            ModelState::Holonomic { vx, vy, heading } => format!(
+                "holonomic (vx {:.0}, vy {:.0} px/s, facing {:.0}°)",
+                vx,
+                vy,
+                heading.to_degrees().rem_euclid(360.0)
+            ),
+        }
+    }
+}
+
+// First message on every connection: who the robot is and its pre-shared key
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Hello {
+    pub id: String,
+    pub key: String,
+    // Codec for everything after the handshake
+    #[serde(default)]
+    pub codec: Codec,
+    // Ask to send telemetry over UDP instead of the TCP stream
+    #[serde(default)]
+    pub udp: bool,
+    // Outline used for the server's collision checks and drawing
+    #[serde(default)]
+    pub footprint: Footprint,
+    // Right of way when robots get too close: the higher level keeps going
+    #[serde(default)]
+    pub priority: u8,
+    // Ask for Neighbours broadcasts, to steer round other robots locally
+    #[serde(default)]
+    pub neighbours: bool,
+}
+
+// Handshake reply: the agreed codec and, if requested, where to send UDP telemetry
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Welcome {
+    pub codec: Codec,
+    pub udp: Option<UdpSession>,
+    // Priority the server uses for the robot, which may override the declared one
+    #[serde(default)]
+    pub priority: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct UdpSession {
+    pub port: u16,
+    // Random per-connection token that prefixes every datagram
+    pub token: u64,
+}
+
+// Another robot near the receiver, as the server sees it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Neighbour {
+    pub id: String,
+    pub x: f32,
+    pub y: f32,
+    // Direction of travel, to tell where it is heading
+    pub speed: f32,
+    pub angle: f32,
+    // Radius of a circle around its footprint
+    pub reach: f32,
+}
+
+// Robot's reply to Ping: the server's send time echoed back, plus the
+// robot clock when the Ping arrived and when the Pong left
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Pong {
+    pub ping_ms: u64,
+    pub received_ms: u64,
+    pub sent_ms: u64,
 }
 
 // Messages sent from Client -> Server
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(tag = "type", content = "payload")]
 pub enum ClientMessage {
-    // Initial handshake or periodic update
+    // Authentication handshake, must be sent before any telemetry
+    Hello(Hello),
+    // Periodic update
     Telemetry(RobotState),
     Disconnect(String),
+    // Answer to Ping, used to estimate clock offset and latency
+    Pong(Pong),
+    // Confirms that the Reliable command with this msg_id was carried out
+    Ack(u64),
+    // How far the robot has got with a mission from GoTo or FollowPath
+    MissionProgress(MissionProgress),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MissionStatus {
+    Started,
+    // Another waypoint reached
+    InProgress,
+    Completed,
+    // Replaced by another route or cancelled on the robot
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MissionProgress {
+    pub mission: u64,
+    pub status: MissionStatus,
+    // Waypoints reached so far; keeps counting round a looping path
+    pub reached: usize,
+    pub total: usize,
+    // Detour waypoints still to drive before the path resumes
+    #[serde(default)]
+    pub detour: usize,
 }
 
 // Messages sent from Server -> Client
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(tag = "type", content = "payload")]
 pub enum ServerMessage {
+    // Handshake accepted, telemetry may follow in the agreed codec
+    Welcome(Welcome),
+    // Handshake refused; the server closes the connection after sending this
+    Rejected(String),
     // Command to force the robot to stop (e.g. collision imminent)
     ForceStop,
     // Command to resume or allow movement
     Resume,
     // Command to set a max speed limit (Global throttle)
     SetSpeedLimit(f32),
+    // Right-of-way level the server now uses for this robot
+    SetPriority(u8),
     // Informational warning
     Warning(String),
-}
\ No newline at end of file
+    // Robots within the server's neighbour radius, sent regularly to robots
+    // that asked in their Hello; each list replaces the previous one
+    Neighbours(Vec<Neighbour>),
+    // Server clock (ms since the Unix epoch); the robot answers with Pong at once
+    Ping(u64),
+    // Drive to a point, then stop. Replaces any route the robot is following.
+    GoTo { mission: u64, x: f32, y: f32 },
+    // Drive through the waypoints in order, like GoTo for each
+    FollowPath { mission: u64, waypoints: Vec<(f32, f32)>, looping: bool },
+    // Abandon the mission and stop, if it is still the one being followed
+    CancelMission(u64),
+    // Traffic management: wait where you are, keeping the route, until released
+    Hold,
+    Release,
+    // Drive these waypoints first, then carry on with the mission's path
+    Detour { mission: u64, waypoints: Vec<(f32, f32)> },
+    // Safety-critical command that the server retransmits until the robot
+    // answers with Ack(msg_id). Robots must carry out each msg_id only once.
+    // msg_ids count up from 1 on each connection and are first sent in order.
+    Reliable { msg_id: u64, command: Box<ServerMessage> },
+}
+
+impl ServerMessage {
+    // Commands that must be acknowledged by the robot
+    pub fn needs_ack(&self) -> bool {
+        matches!(
+            self,
+            ServerMessage::ForceStop
+                | ServerMessage::Resume
+                | ServerMessage::SetSpeedLimit(_)
+                | ServerMessage::SetPriority(_)
+                | ServerMessage::GoTo { .. }
+                | ServerMessage::FollowPath { .. }
+                | ServerMessage::CancelMission(_)
+                | ServerMessage::Hold
+                | ServerMessage::Release
+                | ServerMessage::Detour { .. }
+        )
+    }
+}
+
+// Two robots closer than the proximity warning distance
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProximityPair {
+    pub a: String,
+    pub b: String,
+    // Gap between the two footprints
+    pub distance: f32,
+}
+
+// A stop issued automatically by the server's safety monitor
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SafetyEvent {
+    pub time_ms: u64, // Unix time in milliseconds
+    pub robot: String,
+    pub reason: String,
+}
+
+// Fleet-wide view pushed to external dashboards over the WebSocket stream
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FleetSnapshot {
+    pub time_ms: u64,
+    pub robots: Vec<RobotState>,
+    // IDs whose position is extrapolated rather than recently reported
+    #[serde(default)]
+    pub predicted: Vec<String>,
+    // Each robot's footprint in its own frame, by ID
+    #[serde(default)]
+    pub footprints: BTreeMap<String, Footprint>,
+    pub proximity: Vec<ProximityPair>,
+    pub events: Vec<SafetyEvent>,
+}
+
+// Wall-clock time in milliseconds since the Unix epoch
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
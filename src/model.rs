@@ -0,0 +1,305 @@
+// Drive models for the robot simulation.
+//
+// Every model takes the same command, a wanted speed along a wanted heading,
+// and reports its motion in the same RobotState terms: `speed` along `angle`
+// is the direction of travel, which is what the server extrapolates and
+// checks. Model-specific values (wheel speeds, body heading) go in
+// `RobotState::model`.
+//
+//   Unicycle:           turns on the spot or while driving, then moves forward
+//   Differential drive: two wheels, each with its own acceleration limit;
+//                       turning comes from the difference in wheel speeds
+//   Holonomic:          mecanum/omni wheels; moves in any direction without
+//                       turning, so the body keeps its heading
+use crate::cli::Args;
+use crate::sim::{heading_error, MotionLimits};
+use crate::{ModelState, RobotState};
+use serde::Deserialize;
+use std::f32::consts::TAU;
+
+// Distance between the wheels of a differential-drive robot
+pub const DEFAULT_TRACK_WIDTH: f32 = 20.0;
+// Turn rate (rad/s) a differential drive asks for per radian of heading error
+const HEADING_GAIN: f32 = 5.0;
+
+pub trait RobotModel: Send {
+    // Moves the drive towards `speed` along `heading` within `limits` for
+    // `dt` seconds. Updates `speed`, `angle` and `model` in the state; the
+    // caller integrates the position.
+    fn drive(&mut self, state: &mut RobotState, speed: f32, heading: f32, limits: &MotionLimits, dt: f32);
+
+    // True once the robot has stopped moving and turning
+    fn at_rest(&self, state: &RobotState) -> bool {
+        state.speed == 0.0
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelKind {
+    #[default]
+    Unicycle,
+    DifferentialDrive,
+    Holonomic,
+}
+
+impl std::str::FromStr for ModelKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "unicycle" => Ok(ModelKind::Unicycle),
+            "diff-drive" | "differential_drive" => Ok(ModelKind::DifferentialDrive),
+            "holonomic" | "mecanum" => Ok(ModelKind::Holonomic),
+            _ => Err(format!("Unknown model '{}' (expected unicycle, diff-drive or holonomic)", s)),
+        }
+    }
+}
+
+// Model selection from the command line
+#[derive(Clone, Copy, Debug)]
+pub struct ModelOptions {
+    pub kind: ModelKind,
+    pub track_width: f32,
+}
+
+impl ModelOptions {
+    // `--model unicycle|diff-drive|holonomic` and `--track-width <px>`
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        Ok(Self {
+            kind: args.value("--model").map_or(Ok(ModelKind::default()), str::parse)?,
+            track_width: args.parse_or("--track-width", DEFAULT_TRACK_WIDTH).max(1.0),
+        })
+    }
+
+    // A model starting from the robot's current heading
+    pub fn build(&self, state: &RobotState) -> Box<dyn RobotModel> {
+        match self.kind {
+            ModelKind::Unicycle => Box::new(Unicycle),
+            ModelKind::DifferentialDrive => {
+                Box::new(DifferentialDrive { left: 0.0, right: 0.0, track_width: self.track_width })
+            }
+            ModelKind::Holonomic => Box::new(Holonomic { vx: 0.0, vy: 0.0, heading: state.angle }),
+        }
+    }
+}
+
+// Moves `current` towards `target`, accelerating or braking within the limits
+fn approach(current: f32, target: f32, limits: &MotionLimits, dt: f32) -> f32 {
+    let speeding_up = target.abs() > current.abs() && target * current >= 0.0;
+    let step = if speeding_up { limits.max_accel } else { limits.max_decel } * dt;
+    current + (target - current).clamp(-step, step)
+}
+
+pub struct Unicycle;
+
+impl RobotModel for Unicycle {
+    fn drive(&mut self, state: &mut RobotState, speed: f32, heading: f32, limits: &MotionLimits, dt: f32) {
+        // Turn towards the heading, no faster than the turn rate
+        let error = heading_error(state.angle, heading);
+        let max_turn = limits.max_turn_rate * dt;
+        state.angle = (state.angle + error.clamp(-max_turn, max_turn)).rem_euclid(TAU);
+
+        // Slow down while facing away from the heading, and turn on the spot
+        // when more than 90 degrees off
+        let error = heading_error(state.angle, heading);
+        state.speed = approach(state.speed, speed * error.cos().max(0.0), limits, dt);
+        state.model = ModelState::Unicycle;
+    }
+}
+
+pub struct DifferentialDrive {
+    // Wheel rim speeds, px/s
+    left: f32,
+    right: f32,
+    track_width: f32,
+}
+
+impl RobotModel for DifferentialDrive {
+    fn drive(&mut self, state: &mut RobotState, speed: f32, heading: f32, limits: &MotionLimits, dt: f32) {
+        // Steer in proportion to the heading error, forward speed as for the unicycle
+        let error = heading_error(state.angle, heading);
+        let turn = (HEADING_GAIN * error).clamp(-limits.max_turn_rate, limits.max_turn_rate);
+        let forward = speed * error.cos().max(0.0);
+
+        // Each wheel chases its own target speed within the acceleration limits
+        let half_track = self.track_width / 2.0;
+        self.left = approach(self.left, forward - turn * half_track, limits, dt);
+        self.right = approach(self.right, forward + turn * half_track, limits, dt);
+
+        state.speed = (self.left + self.right) / 2.0;
+        state.angle = (state.angle + (self.right - self.left) / self.track_width * dt).rem_euclid(TAU);
+        state.model = ModelState::DifferentialDrive { left: self.left, right: self.right, track_width: self.track_width };
+    }
+
+    fn at_rest(&self, _state: &RobotState) -> bool {
+        self.left == 0.0 && self.right == 0.0
+    }
+}
+
+pub struct Holonomic {
+    // Velocity in workspace coordinates, px/s
+    vx: f32,
+    vy: f32,
+    // Direction the body faces; never changes since it can drive any way
+    heading: f32,
+}
+
+impl RobotModel for Holonomic {
+    fn drive(&mut self, state: &mut RobotState, speed: f32, heading: f32, limits: &MotionLimits, dt: f32) {
+        // Accelerate straight towards the wanted velocity, no turning first
+        let (tx, ty) = (speed * heading.cos(), speed * heading.sin());
+        let (dx, dy) = (tx - self.vx, ty - self.vy);
+        let change = dx.hypot(dy);
+        if change > 0.0 {
+            let rate = if speed > self.vx.hypot(self.vy) { limits.max_accel } else { limits.max_decel };
+            let step = (rate * dt).min(change) / change;
+            self.vx += dx * step;
+            self.vy += dy * step;
+        }
+
+        state.speed = self.vx.hypot(self.vy);
+        // Keep the last direction of travel once at rest
+        if state.speed > 0.0 {
+            state.angle = self.vy.atan2(self.vx).rem_euclid(TAU);
+        }
+        state.model = ModelState::Holonomic { vx: self.vx, vy: self.vy, heading: self.heading };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    const DT: f32 = 0.02;
+
+    fn state(angle: f32) -> RobotState {
+        RobotState {
+            id: "T".into(),
+            x: 300.0,
+            y: 200.0,
+            speed: 0.0,
+            angle,
+            active: true,
+            color: (0, 0, 0),
+            seq: 0,
+            sent_at_ms: 0,
+            stopping_distance: 0.0,
+            model: ModelState::Unicycle,
+        }
+    }
+
+    fn diff_drive(left: f32, right: f32) -> DifferentialDrive {
+        DifferentialDrive { left, right, track_width: DEFAULT_TRACK_WIDTH }
+    }
+
+    fn wheels(state: &RobotState) -> (f32, f32) {
+        match state.model {
+            ModelState::DifferentialDrive { left, right, .. } => (left, right),
+            _ => panic!("not a differential drive: {:?}", state.model),
+        }
+    }
+
+    // Velocity from the model's own readings: wheel speeds along the body, or
+    // the holonomic velocity
+    fn model_velocity(state: &RobotState) -> (f32, f32) {
+        match state.model {
+            ModelState::Unicycle => (state.speed * state.angle.cos(), state.speed * state.angle.sin()),
+            ModelState::DifferentialDrive { left, right, .. } => {
+                let forward = (left + right) / 2.0;
+                (forward * state.angle.cos(), forward * state.angle.sin())
+            }
+            ModelState::Holonomic { vx, vy, .. } => (vx, vy),
+        }
+    }
+
+    #[test]
+    fn diff_drive_with_equal_wheels_goes_straight() {
+        let mut robot = state(0.5);
+        let mut model = diff_drive(50.0, 50.0);
+        for _ in 0..50 {
+            model.drive(&mut robot, 50.0, 0.5, &MotionLimits::default(), DT);
+        }
+        assert_eq!(wheels(&robot), (50.0, 50.0));
+        assert_eq!(robot.speed, 50.0);
+        assert!((robot.angle - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn diff_drive_with_opposite_wheels_turns_on_the_spot() {
+        // Wanted heading behind the robot: no forward speed, all turn
+        let mut robot = state(0.0);
+        let mut model = diff_drive(0.0, 0.0);
+        model.drive(&mut robot, 50.0, PI - 0.1, &MotionLimits::default(), DT);
+        let (left, right) = wheels(&robot);
+        assert!(right > 0.0 && left == -right);
+        assert_eq!(robot.speed, 0.0);
+        assert!(robot.angle > 0.0);
+    }
+
+    #[test]
+    fn diff_drive_wheels_respect_the_acceleration_limits() {
+        let limits = MotionLimits::default();
+        let mut robot = state(0.0);
+        let mut model = diff_drive(0.0, 0.0);
+        let mut last = (0.0f32, 0.0f32);
+        // Pull away while turning, then brake to rest along the way it faces,
+        // as a stopped robot does
+        for tick in 0..200 {
+            let (speed, heading) = if tick < 100 { (120.0, FRAC_PI_2) } else { (0.0, robot.angle) };
+            model.drive(&mut robot, speed, heading, &limits, DT);
+            let now = wheels(&robot);
+            for (before, after) in [(last.0, now.0), (last.1, now.1)] {
+                let speeding_up = after.abs() > before.abs() && after * before >= 0.0;
+                let limit = if speeding_up { limits.max_accel } else { limits.max_decel };
+                assert!((after - before).abs() <= limit * DT + 1e-3, "wheel went {} -> {}", before, after);
+            }
+            last = now;
+        }
+        assert!(model.at_rest(&robot));
+    }
+
+    #[test]
+    fn holonomic_keeps_its_heading_while_translating() {
+        let mut robot = state(0.3);
+        let mut model = Holonomic { vx: 0.0, vy: 0.0, heading: 0.3 };
+        for _ in 0..100 {
+            model.drive(&mut robot, 80.0, 2.0, &MotionLimits::default(), DT);
+        }
+        assert_eq!(robot.body_heading(), 0.3);
+        assert!((robot.angle - 2.0).abs() < 1e-4);
+        assert!((robot.speed - 80.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn reported_speed_and_angle_match_the_motion() {
+        let limits = MotionLimits::default();
+        let models: Vec<Box<dyn RobotModel>> = vec![
+            Box::new(Unicycle),
+            Box::new(diff_drive(0.0, 0.0)),
+            Box::new(Holonomic { vx: 0.0, vy: 0.0, heading: 0.0 }),
+        ];
+        for mut model in models {
+            let mut robot = state(0.0);
+            // Set off, swing round, then stop
+            for tick in 0..300 {
+                let (speed, heading) = match tick {
+                    0..100 => (100.0, 0.0),
+                    100..200 => (100.0, 2.5),
+                    _ => (0.0, 2.5),
+                };
+                model.drive(&mut robot, speed, heading, &limits, DT);
+                let (vx, vy) = model_velocity(&robot);
+                let reported = (robot.speed * robot.angle.cos(), robot.speed * robot.angle.sin());
+                assert!(
+                    (reported.0 - vx).abs() < 1e-3 && (reported.1 - vy).abs() < 1e-3,
+                    "{:?}: reported {:?}, moving {:?}",
+                    robot.model,
+                    reported,
+                    (vx, vy)
+                );
+            }
+        }
+    }
+}
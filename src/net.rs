@@ -0,0 +1,184 @@
+// Client side of a robot connection, shared by the client binaries: the JSON
+// handshake, then a reader thread and a writer thread in the negotiated codec.
+use crate::cli::Args;
+use crate::codec::{Codec, DEFAULT_MAX_FRAME};
+use crate::datagram::UdpLink;
+use crate::footprint::Footprint;
+use crate::transport::{NetStream, TlsClient};
+use crate::{now_ms, ClientMessage, Hello, Pong, ServerMessage};
+use std::io::BufReader;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+pub const SERVER_ADDR: &str = "127.0.0.1:5050";
+
+// Connection settings from the command line
+#[derive(Clone)]
+pub struct ConnectOptions {
+    pub server_addr: String,
+    // Pre-shared key for the server's robot authentication
+    pub key: String,
+    pub tls: Option<Arc<TlsClient>>,
+    // Wire format requested in the handshake (JSON is easiest to debug)
+    pub codec: Codec,
+    // Largest frame accepted from the server
+    pub max_frame: usize,
+    // Send telemetry over UDP, keeping TCP for commands
+    pub udp: bool,
+    // Outline announced in the Hello
+    pub footprint: Footprint,
+    // Right-of-way level announced in the Hello; the server may override it
+    pub priority: u8,
+    // Ask for neighbour broadcasts and steer round the robots in them
+    pub avoid: bool,
+}
+
+impl ConnectOptions {
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        // --tls-ca turns TLS on; --tls-cert/--tls-key add a client certificate for mutual TLS
+        let tls = match args.value("--tls-ca") {
+            Some(ca) => {
+                let identity = match (args.value("--tls-cert"), args.value("--tls-key")) {
+                    (Some(cert), Some(key)) => Some((cert, key)),
+                    (None, None) => None,
+                    _ => return Err("--tls-cert and --tls-key must be given together".into()),
+                };
+                let server_name = args.value("--tls-server-name").map(String::from);
+                Some(Arc::new(TlsClient::new(ca, identity, server_name)?))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            server_addr: args.value("--server").unwrap_or(SERVER_ADDR).to_string(),
+            key: args
+                .value("--key")
+                .map(String::from)
+                .or_else(|| std::env::var("COBOT_ROBOT_KEY").ok())
+                .unwrap_or_default(),
+            tls,
+            codec: args.value("--codec").map_or(Ok(Codec::Json), str::parse)?,
+            max_frame: args.parse_or("--max-frame", DEFAULT_MAX_FRAME),
+            udp: args.flag("--udp"),
+            footprint: args.value("--footprint").map_or(Ok(Footprint::default()), str::parse)?,
+            priority: match args.value("--priority") {
+                Some(raw) => raw.parse().map_err(|_| format!("Invalid --priority (0-255): {}", raw))?,
+                None => 0,
+            },
+            avoid: args.flag("--avoid"),
+        })
+    }
+}
+
+// Channels to and from the network threads of one connection.
+// `rx` disconnects once the connection is closed or refused.
+pub struct Connection {
+    pub tx: mpsc::Sender<ClientMessage>,
+    pub rx: mpsc::Receiver<ServerMessage>,
+}
+
+// Connects in the background as robot `id`. The handshake reply (Welcome or
+// Rejected) is the first message on `rx`. Connection problems go to `tx_log`.
+pub fn connect(options: &ConnectOptions, id: &str, tx_log: mpsc::Sender<String>) -> Connection {
+    let (tx_to_net, rx_from_robot) = mpsc::channel::<ClientMessage>();
+    let (tx_to_robot, rx_from_net) = mpsc::channel::<ServerMessage>();
+    let tx_pong = tx_to_net.clone();
+
+    let address = options.server_addr.clone();
+    let tls = options.tls.clone();
+    let max_frame = options.max_frame;
+    let codec = options.codec;
+    let hello = Hello {
+        id: id.to_string(),
+        key: options.key.clone(),
+        codec,
+        udp: options.udp,
+        footprint: options.footprint.clone(),
+        priority: options.priority,
+        neighbours: options.avoid,
+    };
+
+    thread::spawn(move || {
+        let stream = match NetStream::connect(&address, tls.as_deref()) {
+            Ok(stream) => stream,
+            Err(e) => {
+                let _ = tx_log.send(format!("Failed to connect to {}: {}", address, e));
+                return;
+            }
+        };
+        let stream_clone = stream.try_clone().expect("Clone failed");
+        let mut writer = stream;
+        let mut reader = BufReader::new(stream_clone);
+
+        // Handshake (always JSON): authenticate and agree on the codec
+        // before any telemetry goes out
+        if Codec::Json.write(&mut writer, &ClientMessage::Hello(hello)).is_err() {
+            return;
+        }
+        let udp = match Codec::Json.read::<ServerMessage>(&mut reader, max_frame) {
+            Ok(Some(ServerMessage::Welcome(welcome))) => {
+                let server_ip = writer.peer_addr().map(|a| a.ip());
+                let link = match (welcome.udp, server_ip) {
+                    // Never send plaintext datagrams alongside an encrypted connection
+                    (Some(_), _) if writer.is_tls() => None,
+                    (Some(session), Ok(ip)) => UdpLink::open(ip, session, codec)
+                        .inspect_err(|e| {
+                            let _ = tx_log.send(format!("UDP unavailable, using TCP: {}", e));
+                        })
+                        .ok(),
+                    _ => None,
+                };
+                let _ = tx_to_robot.send(ServerMessage::Welcome(welcome));
+                link
+            }
+            Ok(Some(msg @ ServerMessage::Rejected(_))) => {
+                let _ = tx_to_robot.send(msg);
+                return;
+            }
+            _ => {
+                let _ = tx_to_robot.send(ServerMessage::Rejected("No handshake reply".into()));
+                return;
+            }
+        };
+
+        // Reader Thread
+        let tx_log_read = tx_log.clone();
+        thread::spawn(move || {
+            loop {
+                match codec.read::<ServerMessage>(&mut reader, max_frame) {
+                    // Answered here rather than by the robot so the timing is not skewed by its tick rate
+                    Ok(Some(ServerMessage::Ping(ping_ms))) => {
+                        let received_ms = now_ms();
+                        let pong = Pong { ping_ms, received_ms, sent_ms: now_ms() };
+                        let _ = tx_pong.send(ClientMessage::Pong(pong));
+                    }
+                    Ok(Some(msg)) => {
+                        let _ = tx_to_robot.send(msg);
+                    }
+                    Ok(None) => break,
+                    Err(e) if e.is_fatal() => {
+                        let _ = tx_log_read.send(format!("Closing connection: {}", e));
+                        break;
+                    }
+                    Err(_) => {}
+                }
+            }
+        });
+
+        // Writer Loop (on this thread)
+        let mut registered = false;
+        while let Ok(msg) = rx_from_robot.recv() {
+            // The first telemetry registers the robot over TCP; later positions go over UDP
+            if let (Some(link), ClientMessage::Telemetry(state), true) = (&udp, &msg, registered) {
+                let _ = link.send(state);
+                continue;
+            }
+            registered |= matches!(msg, ClientMessage::Telemetry(_));
+            if codec.write(&mut writer, &msg).is_err() {
+                break;
+            }
+        }
+    });
+
+    Connection { tx: tx_to_net, rx: rx_from_net }
+}
@@ -0,0 +1,852 @@
+// Robot simulation shared by the client binaries.
+//
+// A fixed-rate thread integrates physics, carries out server commands and
+// publishes telemetry, so a robot keeps moving and reporting whatever its
+// front end is doing (a minimised window, a slow terminal). Front ends lock
+// the shared Robot to read its state and to apply operator inputs.
+use crate::cli::Args;
+use crate::footprint::Footprint;
+use crate::model::{RobotModel, Unicycle};
+use crate::net::Connection;
+use crate::{
+    now_ms, ClientMessage, MissionProgress, MissionStatus, Neighbour, RobotState, ServerMessage, BOUNDARY_HEIGHT,
+    BOUNDARY_WIDTH,
+};
+use rand::Rng;
+use serde::Deserialize;
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_RATE_HZ: f32 = 50.0;
+pub const MAX_RATE_HZ: f32 = 500.0;
+// A waypoint counts as reached within this distance, unless the route says otherwise
+pub const WAYPOINT_TOLERANCE: f32 = 10.0;
+// Local avoidance: neighbours further than this gap away are ignored, px
+const AVOID_RANGE: f32 = 100.0;
+// Gap kept to a neighbour ahead, clear of the server's 30 px collision stop, px
+const STANDOFF: f32 = 40.0;
+// How hard a neighbour at no gap pushes, against the pull of the wanted heading
+const AVOID_GAIN: f32 = 3.0;
+// Neighbours the server has not repeated for this long are forgotten
+const NEIGHBOURS_TIMEOUT: Duration = Duration::from_millis(500);
+
+// How hard the robot can speed up, brake and turn
+#[derive(Clone, Copy, Debug)]
+pub struct MotionLimits {
+    pub max_accel: f32,     // px/s²
+    pub max_decel: f32,     // px/s², also used for ForceStop
+    pub max_turn_rate: f32, // rad/s
+}
+
+impl Default for MotionLimits {
+    fn default() -> Self {
+        Self { max_accel: 150.0, max_decel: 300.0, max_turn_rate: PI }
+    }
+}
+
+impl MotionLimits {
+    // `--max-accel`, `--max-decel` (px/s²) and `--max-turn-rate` (degrees/s)
+    pub fn from_args(args: &Args) -> Self {
+        let default = Self::default();
+        Self {
+            max_accel: args.parse_or("--max-accel", default.max_accel).max(1.0),
+            max_decel: args.parse_or("--max-decel", default.max_decel).max(1.0),
+            max_turn_rate: args.parse_or("--max-turn-rate", default.max_turn_rate.to_degrees()).max(1.0).to_radians(),
+        }
+    }
+
+    // Distance covered while braking to rest from `speed`
+    pub fn stopping_distance(&self, speed: f32) -> f32 {
+        speed * speed / (2.0 * self.max_decel)
+    }
+}
+
+// Signed smallest turn from heading `from` to heading `to`, in [-PI, PI)
+pub(crate) fn heading_error(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(TAU) - PI
+}
+
+// Waypoints the robot steers through in order
+#[derive(Clone, Debug)]
+pub struct Route {
+    pub waypoints: Vec<(f32, f32)>,
+    // Start again from the first waypoint after the last one
+    pub looping: bool,
+    // Distance at which a waypoint counts as reached
+    pub tolerance: f32,
+    // Set when the server dispatched this route; progress is reported under it
+    pub mission: Option<u64>,
+    // Driven before the next waypoint, e.g. round a robot in the way; not counted as reached
+    pub detour: Vec<(f32, f32)>,
+    next: usize,
+    reached: usize,
+}
+
+// Route file: `{"waypoints": [[100, 100], [500, 100]], "looping": true, "tolerance": 15}`
+// (only `waypoints` is required)
+#[derive(Deserialize)]
+struct RouteFile {
+    waypoints: Vec<(f32, f32)>,
+    #[serde(default)]
+    looping: bool,
+    tolerance: Option<f32>,
+}
+
+impl Route {
+    pub fn new(waypoints: Vec<(f32, f32)>, looping: bool) -> Self {
+        Self { waypoints, looping, tolerance: WAYPOINT_TOLERANCE, mission: None, detour: Vec::new(), next: 0, reached: 0 }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let file: RouteFile = serde_json::from_str(&text).map_err(|e| format!("Invalid route file {}: {}", path, e))?;
+        if file.waypoints.is_empty() {
+            return Err(format!("Route file {} has no waypoints", path));
+        }
+        let mut route = Route::new(file.waypoints, file.looping);
+        route.tolerance = file.tolerance.unwrap_or(WAYPOINT_TOLERANCE).max(1.0);
+        Ok(route)
+    }
+
+    // Waypoint currently steered towards
+    pub fn target(&self) -> Option<(f32, f32)> {
+        self.detour.first().or(self.waypoints.get(self.next)).copied()
+    }
+
+    // Index of the waypoint currently steered towards
+    pub fn next_index(&self) -> usize {
+        self.next
+    }
+
+    // Waypoints reached so far, counting every lap of a looping route
+    pub fn reached(&self) -> usize {
+        self.reached
+    }
+
+    // True while heading for the last waypoint of a route that ends there
+    pub fn on_final_leg(&self) -> bool {
+        !self.looping && self.detour.is_empty() && self.next + 1 == self.waypoints.len()
+    }
+
+    // Advances past every waypoint already within tolerance of (x, y)
+    fn advance(&mut self, x: f32, y: f32) {
+        while let Some((tx, ty)) = self.target() {
+            if (tx - x).hypot(ty - y) > self.tolerance {
+                return;
+            }
+            if !self.detour.is_empty() {
+                self.detour.remove(0);
+                continue;
+            }
+            self.next += 1;
+            self.reached += 1;
+            if self.looping && self.next == self.waypoints.len() {
+                self.next = 0;
+                // A single-point loop would spin here forever
+                if self.waypoints.len() == 1 {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+pub struct Robot {
+    pub state: RobotState,
+    pub target_speed: f32,
+    // Heading the robot turns towards at its turn rate
+    pub target_heading: f32,
+    pub speed_limit: f32, // Controlled by server
+    pub limits: MotionLimits,
+    // How commands turn into motion (unicycle unless a front end picks another)
+    pub model: Box<dyn RobotModel>,
+    // A ForceStop is braking the robot; it backs off the obstacle once at rest
+    bounce_pending: bool,
+    // Told by the server's traffic manager to wait; the robot stays active
+    pub held: bool,
+    // Stopped by a ForceStop that the server has not lifted with Resume yet
+    pub server_stop: bool,
+    // Right-of-way level the server uses for this robot, once it has said
+    pub priority: Option<u8>,
+    // Steer round the neighbours the server reports (asked for with --avoid)
+    pub avoid: bool,
+    // Radius of a circle around the robot's footprint, for the gap to neighbours
+    pub reach: f32,
+    // Latest robots nearby from the server, and when they arrived
+    pub neighbours: Vec<Neighbour>,
+    neighbours_at: Instant,
+    // Steering round a neighbour this tick
+    pub avoiding: bool,
+
+    // Novel Feature: Wander Mode
+    pub wander_mode: bool,
+    last_wander_change: Instant,
+    // Steers the robot instead of the heading input while set
+    pub route: Option<Route>,
+    // Last progress sent for a server mission
+    mission_report: Option<MissionProgress>,
+
+    pub connection_status: String,
+    net: Option<Connection>,
+    // Highest Reliable msg_id carried out on this connection. The server numbers
+    // each connection's commands upwards and first sends them in that order, so
+    // anything at or below it is a retransmission.
+    last_msg_id: u64,
+    tx_log: mpsc::Sender<String>,
+}
+
+pub type SharedRobot = Arc<Mutex<Robot>>;
+
+impl Robot {
+    pub fn new(state: RobotState, tx_log: mpsc::Sender<String>) -> Self {
+        Self {
+            target_heading: state.angle,
+            state,
+            target_speed: 50.0,
+            speed_limit: 200.0,
+            limits: MotionLimits::default(),
+            model: Box::new(Unicycle),
+            bounce_pending: false,
+            held: false,
+            server_stop: false,
+            priority: None,
+            avoid: false,
+            reach: Footprint::default().reach(),
+            neighbours: Vec::new(),
+            neighbours_at: Instant::now(),
+            avoiding: false,
+            wander_mode: false,
+            last_wander_change: Instant::now(),
+            route: None,
+            mission_report: None,
+            connection_status: "Disconnected".to_string(),
+            net: None,
+            last_msg_id: 0,
+            tx_log,
+        }
+    }
+
+    pub fn log(&self, line: impl Into<String>) {
+        let _ = self.tx_log.send(line.into());
+    }
+
+    // For threads that log on the robot's behalf, such as its network threads
+    pub fn log_sender(&self) -> mpsc::Sender<String> {
+        self.tx_log.clone()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.net.is_some()
+    }
+
+    // Takes over a new connection; its telemetry starts on the next tick.
+    // A traffic hold belonged to the old connection; the server holds the
+    // robot again if it still needs to.
+    pub fn attach(&mut self, net: Connection) {
+        self.net = Some(net);
+        self.last_msg_id = 0;
+        self.priority = None;
+        self.held = false;
+        self.connection_status = "Connected".to_string();
+    }
+
+    // One simulation step of `dt` seconds
+    pub fn tick(&mut self, dt: f32) {
+        self.receive_commands();
+        self.update_physics(dt);
+        self.report_mission();
+        self.send_telemetry();
+    }
+
+    fn receive_commands(&mut self) {
+        loop {
+            let msg = match self.net.as_ref().map(|net| net.rx.try_recv()) {
+                Some(Ok(msg)) => msg,
+                Some(Err(TryRecvError::Disconnected)) => {
+                    // The server forgets the robot and will never send Release
+                    self.net = None;
+                    self.held = false;
+                    self.connection_status = "Disconnected".to_string();
+                    self.log("Connection lost.");
+                    return;
+                }
+                Some(Err(TryRecvError::Empty)) | None => return,
+            };
+
+            if let ServerMessage::Reliable { msg_id, command } = msg {
+                // Retransmissions are acknowledged again but carried out only once
+                if msg_id > self.last_msg_id {
+                    self.last_msg_id = msg_id;
+                    self.handle_command(*command);
+                }
+                if let Some(net) = &self.net {
+                    let _ = net.tx.send(ClientMessage::Ack(msg_id));
+                }
+            } else {
+                self.handle_command(msg);
+            }
+        }
+    }
+
+    fn update_physics(&mut self, dt: f32) {
+        if !self.state.active {
+            // Stopped robots brake to rest along their direction of travel
+            let heading = self.state.angle;
+            self.model.drive(&mut self.state, 0.0, heading, &self.limits, dt);
+            self.advance(dt);
+            if self.bounce_pending && self.model.at_rest(&self.state) {
+                self.bounce();
+            }
+            self.state.stopping_distance = self.limits.stopping_distance(self.state.speed);
+            return;
+        }
+        self.bounce_pending = false;
+
+        // Novel Feature: Wander Logic
+        let now = Instant::now();
+        if self.wander_mode && now.duration_since(self.last_wander_change).as_secs_f32() > 2.0 {
+            let mut rng = rand::thread_rng();
+            self.target_heading += rng.gen_range(-1.0..1.0); // Turn slightly
+            self.last_wander_change = now;
+        }
+
+        // Waypoint following
+        let mut arrival_speed = f32::MAX;
+        if let Some(route) = &mut self.route {
+            route.advance(self.state.x, self.state.y);
+            match route.target() {
+                Some((tx, ty)) => {
+                    let (dx, dy) = (tx - self.state.x, ty - self.state.y);
+                    self.target_heading = dy.atan2(dx);
+                    // Slow down to come to rest at the end of the route instead of overshooting it
+                    if route.on_final_leg() {
+                        arrival_speed = (2.0 * self.limits.max_decel * dx.hypot(dy)).sqrt();
+                    }
+                }
+                None => {
+                    let mission = route.mission;
+                    let progress = MissionProgress {
+                        mission: mission.unwrap_or(0),
+                        status: MissionStatus::Completed,
+                        reached: route.reached(),
+                        total: route.waypoints.len(),
+                        detour: 0,
+                    };
+                    self.route = None;
+                    self.state.active = false;
+                    match mission {
+                        Some(id) => {
+                            self.log(format!("Route complete (mission #{})", id));
+                            self.send_progress(progress);
+                        }
+                        None => self.log("Route complete"),
+                    }
+                    return;
+                }
+            }
+        }
+
+        // Apply Speed Limit, then let the drive model work out the motion. A
+        // held robot brakes but keeps facing its way.
+        let mut wanted = self.target_speed.min(self.speed_limit).min(arrival_speed).max(0.0);
+        if self.held {
+            wanted = 0.0;
+        }
+        let (heading, wanted) = self.avoid_neighbours(self.target_heading, wanted);
+        self.model.drive(&mut self.state, wanted, heading, &self.limits, dt);
+
+        self.advance(dt);
+        self.state.stopping_distance = self.limits.stopping_distance(self.state.speed);
+    }
+
+    // Local avoidance, potential-field style: the wanted heading pulls, and each
+    // neighbour within AVOID_RANGE pushes the robot away, harder the closer it
+    // is. A neighbour ahead also pushes it to its right, so two robots meeting
+    // head on both keep right and pass. The steer stays within 90° of the
+    // wanted heading, and the speed is capped so the robot can brake STANDOFF
+    // short of a neighbour ahead, leaving it half the room in case it is
+    // braking towards us. Returns the heading and speed to drive at.
+    fn avoid_neighbours(&mut self, heading: f32, speed: f32) -> (f32, f32) {
+        if self.neighbours_at.elapsed() > NEIGHBOURS_TIMEOUT {
+            self.neighbours.clear();
+        }
+        self.avoiding = false;
+        if !self.avoid || speed == 0.0 {
+            return (heading, speed);
+        }
+
+        // Gap to each neighbour close by, where it has got to since the list
+        // was sent, and the unit vector towards it
+        let age = self.neighbours_at.elapsed().as_secs_f32();
+        let near: Vec<(f32, f32, f32)> = self
+            .neighbours
+            .iter()
+            .filter_map(|n| {
+                let x = n.x + n.speed * n.angle.cos() * age;
+                let y = n.y + n.speed * n.angle.sin() * age;
+                let (dx, dy) = (x - self.state.x, y - self.state.y);
+                let dist = dx.hypot(dy).max(f32::EPSILON);
+                let gap = dist - n.reach - self.reach;
+                (gap < AVOID_RANGE).then_some((gap, dx / dist, dy / dist))
+            })
+            .collect();
+        if near.is_empty() {
+            return (heading, speed);
+        }
+        self.avoiding = true;
+
+        let (mut fx, mut fy) = (heading.cos(), heading.sin());
+        // To the right of the heading, with y pointing down
+        let (rx, ry) = (-heading.sin(), heading.cos());
+        for &(gap, ux, uy) in &near {
+            let push = AVOID_GAIN * (1.0 - gap / AVOID_RANGE);
+            let ahead = (ux * heading.cos() + uy * heading.sin()).max(0.0);
+            fx += push * (ahead * rx - ux);
+            fy += push * (ahead * ry - uy);
+        }
+        let steer = heading + heading_error(heading, fy.atan2(fx)).clamp(-FRAC_PI_2, FRAC_PI_2);
+
+        // Neighbours within 60° of the steer
+        let mut speed = speed;
+        for &(gap, ux, uy) in &near {
+            if ux * steer.cos() + uy * steer.sin() > 0.5 {
+                let room = (gap - STANDOFF).max(0.0) / 2.0;
+                speed = speed.min((2.0 * self.limits.max_decel * room).sqrt());
+            }
+        }
+        (steer, speed)
+    }
+
+    // Moves along the current heading at the current speed
+    fn advance(&mut self, dt: f32) {
+        self.state.x += self.state.speed * self.state.angle.cos() * dt;
+        self.state.y += self.state.speed * self.state.angle.sin() * dt;
+
+        // Simple local boundary clamp (client side prediction)
+        // FIXED: Clamp strictly to visible area (0.0 to WIDTH).
+        // The Server triggers alerts at < 10.0 and > WIDTH - 10.0, so hitting 0.0 or WIDTH
+        // will successfully trigger the stop logic without the robot disappearing off-screen.
+        self.state.x = self.state.x.clamp(0.0, BOUNDARY_WIDTH);
+        self.state.y = self.state.y.clamp(0.0, BOUNDARY_HEIGHT);
+    }
+
+    // BOUNCE LOGIC, once a ForceStop has braked the robot to rest:
+    // 1. Aim 180 degrees round (turned on the spot when Go is pressed)
+    // 2. Hop slightly away from the wall
+    // This prevents being "stuck" in the wall when you press Go
+    fn bounce(&mut self) {
+        self.bounce_pending = false;
+        self.target_heading = self.state.angle + PI;
+        self.state.x += 15.0 * self.target_heading.cos();
+        self.state.y += 15.0 * self.target_heading.sin();
+
+        // Ensure the hop doesn't push us out of bounds again
+        self.state.x = self.state.x.clamp(0.0, BOUNDARY_WIDTH);
+        self.state.y = self.state.y.clamp(0.0, BOUNDARY_HEIGHT);
+        self.log("CMD: Came to rest (Turn 180° queued - Press GO to escape)");
+    }
+
+    // Reports a newly started mission and each waypoint reached, and a
+    // mission dropped before completion (cancelled or replaced on the robot)
+    fn report_mission(&mut self) {
+        let current = self.route.as_ref().and_then(|route| {
+            Some(MissionProgress {
+                mission: route.mission?,
+                status: MissionStatus::InProgress,
+                reached: route.reached(),
+                total: route.waypoints.len(),
+                detour: route.detour.len(),
+            })
+        });
+        if let Some(last) = &self.mission_report
+            && current.as_ref().is_none_or(|c| c.mission != last.mission)
+        {
+            let cancelled = MissionProgress { status: MissionStatus::Cancelled, ..last.clone() };
+            self.log(format!("Mission #{} cancelled", cancelled.mission));
+            self.send_progress(cancelled);
+        }
+
+        if let Some(mut current) = current {
+            match &self.mission_report {
+                Some(last)
+                    if last.mission == current.mission
+                        && last.reached == current.reached
+                        && last.detour == current.detour =>
+                {
+                    return;
+                }
+                Some(last) if last.mission == current.mission => {}
+                _ => current.status = MissionStatus::Started,
+            }
+            self.send_progress(current);
+        }
+    }
+
+    // Only a mission still under way is remembered, so a finished one is
+    // not reported cancelled as well
+    fn send_progress(&mut self, progress: MissionProgress) {
+        let under_way = matches!(progress.status, MissionStatus::Started | MissionStatus::InProgress);
+        self.mission_report = under_way.then(|| progress.clone());
+        if let Some(net) = &self.net {
+            let _ = net.tx.send(ClientMessage::MissionProgress(progress));
+        }
+    }
+
+    // Follows a route dispatched by the server, starting at once
+    fn start_mission(&mut self, mission: u64, waypoints: Vec<(f32, f32)>, looping: bool) {
+        let mut route = Route::new(waypoints, looping);
+        route.mission = Some(mission);
+        self.route = Some(route);
+        // A mission never clears a stop; a stopped robot sets off on Resume
+        if !self.state.active {
+            self.log(format!("Stopped: mission #{} waits for a Resume", mission));
+        }
+    }
+
+    // Applies one command from the server
+    fn handle_command(&mut self, msg: ServerMessage) {
+        match msg {
+            ServerMessage::Welcome(welcome) => {
+                let transport = if welcome.udp.is_some() { "UDP telemetry" } else { "TCP telemetry" };
+                self.priority = Some(welcome.priority);
+                self.log(format!(
+                    "SERVER: Authenticated ({:?}, {}, priority {})",
+                    welcome.codec, transport, welcome.priority
+                ));
+            }
+            ServerMessage::Rejected(reason) => {
+                // Server closes the connection; dropping the sender ends the writer thread
+                self.net = None;
+                self.connection_status = "Rejected".to_string();
+                self.log(format!("SERVER REJECTED CONNECTION: {}", reason));
+            }
+            ServerMessage::ForceStop => {
+                self.server_stop = true;
+                // FIXED: Only process stop if we are currently active (prevents logic loops)
+                if self.state.active {
+                    // Brake at full deceleration instead of halting on the spot
+                    self.state.active = false;
+                    self.bounce_pending = true;
+                    self.log(format!(
+                        "CMD: STOPPED (braking from {:.0} px/s over {:.1} px)",
+                        self.state.speed,
+                        self.limits.stopping_distance(self.state.speed)
+                    ));
+                    if self.model.at_rest(&self.state) {
+                        self.bounce();
+                    }
+                }
+            }
+            ServerMessage::Resume => {
+                self.state.active = true;
+                self.server_stop = false;
+                self.log("SERVER CMD: RESUME");
+            }
+            ServerMessage::SetSpeedLimit(limit) => {
+                self.speed_limit = limit;
+                self.log(format!("SERVER CMD: Speed Limit {}", limit));
+            }
+            ServerMessage::SetPriority(priority) => {
+                self.priority = Some(priority);
+                self.log(format!("SERVER CMD: Priority {}", priority));
+            }
+            ServerMessage::Warning(txt) => {
+                self.log(format!("WARNING: {}", txt));
+            }
+            ServerMessage::Neighbours(neighbours) => {
+                self.neighbours = neighbours;
+                self.neighbours_at = Instant::now();
+            }
+            ServerMessage::GoTo { mission, x, y } => {
+                self.log(format!("SERVER CMD: Go to ({:.0}, {:.0}) (mission #{})", x, y, mission));
+                self.start_mission(mission, vec![(x, y)], false);
+            }
+            ServerMessage::FollowPath { mission, waypoints, looping } => {
+                self.log(format!(
+                    "SERVER CMD: Follow {} waypoint(s){} (mission #{})",
+                    waypoints.len(),
+                    if looping { ", looping" } else { "" },
+                    mission
+                ));
+                self.start_mission(mission, waypoints, looping);
+            }
+            ServerMessage::CancelMission(mission) => {
+                // Reported back as cancelled on the next tick
+                if self.route.as_ref().is_some_and(|r| r.mission == Some(mission)) {
+                    self.route = None;
+                    self.state.active = false;
+                    self.log(format!("SERVER CMD: Cancel mission #{}", mission));
+                }
+            }
+            ServerMessage::Hold => {
+                if !self.held {
+                    self.held = true;
+                    self.log("SERVER CMD: HOLD (traffic)");
+                }
+            }
+            ServerMessage::Release => {
+                if self.held {
+                    self.held = false;
+                    self.log("SERVER CMD: RELEASE (traffic)");
+                }
+            }
+            ServerMessage::Detour { mission, waypoints } => {
+                if let Some(route) = self.route.as_mut().filter(|r| r.mission == Some(mission)) {
+                    route.detour = waypoints;
+                    let count = route.detour.len();
+                    self.log(format!("SERVER CMD: Detour via {} waypoint(s) (mission #{})", count, mission));
+                }
+            }
+            // The server never nests envelopes, and pings are answered by the network thread
+            ServerMessage::Reliable { .. } | ServerMessage::Ping(_) => {}
+        }
+    }
+
+    fn send_telemetry(&mut self) {
+        if let Some(net) = &self.net {
+            self.state.seq += 1;
+            self.state.sent_at_ms = now_ms();
+            let _ = net.tx.send(ClientMessage::Telemetry(self.state.clone()));
+        }
+    }
+}
+
+// `--rate` in Hz, or `default` when it is not given
+pub fn rate_from_args(args: &Args, default: f32) -> Result<f32, String> {
+    let rate = args.parse_or("--rate", default);
+    if !rate.is_finite() {
+        return Err(format!("Invalid --rate: {} (expected a finite number of Hz)", rate));
+    }
+    Ok(rate)
+}
+
+// Ticks `robot` at `rate_hz` for as long as the process runs. Each tick
+// integrates the real time elapsed, so a late tick does not slow the robot.
+pub fn spawn(robot: SharedRobot, rate_hz: f32) {
+    spawn_many(vec![robot], rate_hz);
+}
+
+// Same as `spawn`, for several robots sharing one thread
+pub fn spawn_many(robots: Vec<SharedRobot>, rate_hz: f32) {
+    let period = Duration::from_secs_f32(1.0 / rate_hz.clamp(1.0, MAX_RATE_HZ));
+    thread::spawn(move || {
+        let mut last = Instant::now();
+        let mut next = last + period;
+        loop {
+            thread::sleep(next.saturating_duration_since(Instant::now()));
+            let now = Instant::now();
+            let dt = now.duration_since(last).as_secs_f32();
+            for robot in &robots {
+                robot.lock().unwrap().tick(dt);
+            }
+            last = now;
+            // Skip missed ticks instead of bursting to catch up
+            next = (next + period).max(now);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn robot() -> Robot {
+        let state = RobotState {
+            id: "T".into(),
+            x: 300.0,
+            y: 200.0,
+            speed: 0.0,
+            angle: 0.0,
+            active: true,
+            color: (0, 0, 0),
+            seq: 0,
+            sent_at_ms: 0,
+            stopping_distance: 0.0,
+            model: Default::default(),
+        };
+        Robot::new(state, mpsc::channel().0)
+    }
+
+    // Attaches the robot to channels standing in for the server
+    fn attach(robot: &mut Robot) -> (mpsc::Sender<ServerMessage>, mpsc::Receiver<ClientMessage>) {
+        let (tx_to_robot, rx) = mpsc::channel();
+        let (tx, rx_from_robot) = mpsc::channel();
+        robot.attach(Connection { tx, rx });
+        (tx_to_robot, rx_from_robot)
+    }
+
+    fn connected() -> (Robot, mpsc::Sender<ServerMessage>, mpsc::Receiver<ClientMessage>) {
+        let mut robot = robot();
+        let (server, from_robot) = attach(&mut robot);
+        (robot, server, from_robot)
+    }
+
+    fn reliable(msg_id: u64, command: ServerMessage) -> ServerMessage {
+        ServerMessage::Reliable { msg_id, command: Box::new(command) }
+    }
+
+    #[test]
+    fn retransmitted_commands_are_acked_but_carried_out_once() {
+        let (mut robot, server, from_robot) = connected();
+        robot.state.active = false;
+        server.send(reliable(2, ServerMessage::Resume)).unwrap();
+        robot.receive_commands();
+        assert!(robot.state.active);
+
+        // Stopped locally since; a repeat of #2 or a late #1 must not resume it
+        robot.state.active = false;
+        server.send(reliable(2, ServerMessage::Resume)).unwrap();
+        server.send(reliable(1, ServerMessage::Resume)).unwrap();
+        robot.receive_commands();
+        assert!(!robot.state.active);
+
+        let acks: Vec<u64> = from_robot
+            .try_iter()
+            .filter_map(|m| match m {
+                ClientMessage::Ack(id) => Some(id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(acks, vec![2, 2, 1]);
+    }
+
+    const DT: f32 = 0.02;
+
+    #[test]
+    fn a_server_stop_stays_in_force_until_resume() {
+        let mut robot = robot();
+        robot.handle_command(ServerMessage::ForceStop);
+        assert!(robot.server_stop);
+        // Finishing a route is a stop of the robot's own; the server's still stands
+        robot.route = Some(Route::new(vec![(robot.state.x, robot.state.y)], false));
+        robot.state.active = true;
+        robot.update_physics(DT);
+        assert!(!robot.state.active && robot.server_stop);
+        robot.handle_command(ServerMessage::Resume);
+        assert!(robot.state.active && !robot.server_stop);
+    }
+
+    #[test]
+    fn a_hold_does_not_outlive_the_connection() {
+        let (mut robot, server, _from_robot) = connected();
+        robot.target_speed = 100.0;
+        server.send(ServerMessage::Hold).unwrap();
+        robot.tick(DT);
+        assert!(robot.held);
+        assert_eq!(robot.state.speed, 0.0);
+
+        // The server drops the robot without a Release
+        drop(server);
+        robot.tick(DT);
+        assert!(!robot.is_connected());
+        assert!(!robot.held);
+
+        let _link = attach(&mut robot);
+        robot.tick(DT);
+        assert!(robot.state.speed > 0.0);
+    }
+
+    #[test]
+    fn speeds_up_at_max_accel_to_the_target_speed() {
+        let mut robot = robot();
+        robot.target_speed = 100.0;
+        for _ in 0..10 {
+            robot.update_physics(DT);
+        }
+        let expected = 10.0 * robot.limits.max_accel * DT;
+        assert!((robot.state.speed - expected).abs() < 1e-3);
+        for _ in 0..30 {
+            robot.update_physics(DT);
+        }
+        assert_eq!(robot.state.speed, 100.0);
+    }
+
+    #[test]
+    fn force_stop_brakes_within_the_reported_stopping_distance() {
+        let mut robot = robot();
+        robot.target_speed = 100.0;
+        robot.state.speed = 100.0;
+        robot.update_physics(DT);
+        let reported = robot.state.stopping_distance;
+        assert!((reported - 100.0 * 100.0 / (2.0 * robot.limits.max_decel)).abs() < 1e-3);
+
+        robot.handle_command(ServerMessage::ForceStop);
+        assert!(!robot.state.active);
+        // Still moving: braking, not halted on the spot
+        assert_eq!(robot.state.speed, 100.0);
+
+        let start = robot.state.x;
+        let mut furthest = start;
+        for _ in 0..100 {
+            robot.update_physics(DT);
+            // Before the bounce off the obstacle once at rest
+            if robot.state.speed > 0.0 {
+                furthest = robot.state.x;
+                let v = robot.state.speed;
+                assert!((robot.state.stopping_distance - v * v / (2.0 * robot.limits.max_decel)).abs() < 1e-3);
+            }
+        }
+        assert_eq!(robot.state.speed, 0.0);
+        assert_eq!(robot.state.stopping_distance, 0.0);
+        let travelled = furthest - start;
+        assert!(travelled > reported * 0.8 && travelled <= reported + 1e-3, "braked over {} px", travelled);
+    }
+
+    fn neighbour(x: f32, y: f32) -> Neighbour {
+        Neighbour { id: "N".into(), x, y, speed: 0.0, angle: 0.0, reach: 10.0 }
+    }
+
+    // Robot at (300, 200) facing `heading` that avoids the given neighbours
+    fn avoiding(heading: f32, neighbours: Vec<Neighbour>) -> Robot {
+        let mut robot = robot();
+        robot.state.angle = heading;
+        robot.avoid = true;
+        robot.neighbours = neighbours;
+        robot.neighbours_at = Instant::now();
+        robot
+    }
+
+    #[test]
+    fn robots_meeting_head_on_both_keep_right_and_slow_down() {
+        // 100 px apart nose to nose, an 80 px gap between their footprints
+        let mut a = avoiding(0.0, vec![neighbour(400.0, 200.0)]);
+        let (steer, speed) = a.avoid_neighbours(0.0, 200.0);
+        assert!(a.avoiding);
+        // Right of +x is down the screen
+        assert!(steer > 0.0 && steer < FRAC_PI_2);
+        // Slow enough to brake STANDOFF short, leaving the other half the room
+        let room = (80.0 - STANDOFF) / 2.0;
+        assert!((speed - (2.0 * a.limits.max_decel * room).sqrt()).abs() < 1e-3);
+
+        let mut b = avoiding(PI, vec![neighbour(300.0, 200.0)]);
+        b.state.x = 400.0;
+        let (steer, _) = b.avoid_neighbours(PI, 200.0);
+        // Right of -x is up the screen, so they pass each other
+        assert!(steer.sin() < 0.0);
+    }
+
+    #[test]
+    fn steer_stays_within_90_degrees_of_the_wanted_heading() {
+        let mut robot = avoiding(0.0, vec![neighbour(325.0, 200.0)]);
+        let (steer, speed) = robot.avoid_neighbours(0.0, 100.0);
+        assert!((steer - FRAC_PI_2).abs() < 1e-4);
+        // Already inside the standoff, but the neighbour is no longer ahead of the steer
+        assert_eq!(speed, 100.0);
+    }
+
+    #[test]
+    fn far_or_stale_neighbours_are_ignored() {
+        let mut robot = avoiding(0.0, vec![neighbour(500.0, 200.0)]);
+        assert_eq!(robot.avoid_neighbours(0.0, 100.0), (0.0, 100.0));
+        assert!(!robot.avoiding);
+
+        let mut robot = avoiding(0.0, vec![neighbour(340.0, 200.0)]);
+        robot.neighbours_at = Instant::now() - NEIGHBOURS_TIMEOUT - Duration::from_millis(1);
+        assert_eq!(robot.avoid_neighbours(0.0, 100.0), (0.0, 100.0));
+        assert!(robot.neighbours.is_empty());
+        assert!(!robot.avoiding);
+    }
+}
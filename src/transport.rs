@@ -0,0 +1,211 @@
+// Byte transport between clients and server: plain TCP or TLS.
+//
+// Both binaries split a connection into a reader thread and a writer thread.
+// A rustls stream cannot be cloned like a TcpStream, so TLS connections share
+// one session behind a mutex and the reader polls with a short socket timeout.
+// Between polls the reader lets go of the lock and pauses briefly; the mutex is
+// not fair, so without the pause it would take the lock straight back and a
+// waiting writer (a ForceStop, say) could be held off indefinitely.
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TLS_POLL: Duration = Duration::from_millis(10);
+const TLS_YIELD: Duration = Duration::from_millis(1); // Reader pause between polls, outside the lock
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+trait TlsIo: Read + Write + Send {}
+impl<T: Read + Write + Send> TlsIo for T {}
+
+pub struct NetStream {
+    tcp: TcpStream,
+    tls: Option<Arc<Mutex<Box<dyn TlsIo>>>>,
+    read_timeout: Arc<Mutex<Option<Duration>>>,
+    // Robot ID taken from a verified client certificate (mutual TLS only)
+    peer_identity: Option<String>,
+}
+
+impl NetStream {
+    pub fn plain(tcp: TcpStream) -> Self {
+        Self { tcp, tls: None, read_timeout: Arc::new(Mutex::new(None)), peer_identity: None }
+    }
+
+    pub fn connect(addr: &str, tls: Option<&TlsClient>) -> io::Result<Self> {
+        let tcp = TcpStream::connect(addr)?;
+        let Some(tls) = tls else { return Ok(Self::plain(tcp)) };
+
+        let host = addr.rsplit_once(':').map_or(addr, |(h, _)| h);
+        let name = tls.server_name.clone().unwrap_or_else(|| host.to_string());
+        let name = ServerName::try_from(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let conn = ClientConnection::new(tls.config.clone(), name).map_err(io::Error::other)?;
+        Self::handshake(StreamOwned::new(conn, tcp), |_| None)
+    }
+
+    // Server side of a TLS connection; fails if the handshake or client certificate check fails
+    pub fn accept_tls(tcp: TcpStream, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let conn = ServerConnection::new(config).map_err(io::Error::other)?;
+        Self::handshake(StreamOwned::new(conn, tcp), |s: &StreamOwned<ServerConnection, TcpStream>| {
+            s.conn.peer_certificates()?.first().and_then(certificate_identity)
+        })
+    }
+
+    fn handshake<C, S>(mut stream: StreamOwned<C, TcpStream>, identity: impl Fn(&StreamOwned<C, TcpStream>) -> Option<String>) -> io::Result<Self>
+    where
+        C: std::ops::DerefMut<Target = rustls::ConnectionCommon<S>> + Send + 'static,
+        S: rustls::SideData + Send + 'static,
+    {
+        stream.sock.set_read_timeout(Some(TLS_HANDSHAKE_TIMEOUT))?;
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+        stream.sock.set_read_timeout(Some(TLS_POLL))?;
+
+        let tcp = stream.sock.try_clone()?;
+        let peer_identity = identity(&stream);
+        Ok(Self {
+            tcp,
+            tls: Some(Arc::new(Mutex::new(Box::new(stream)))),
+            read_timeout: Arc::new(Mutex::new(None)),
+            peer_identity,
+        })
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            tcp: self.tcp.try_clone()?,
+            tls: self.tls.clone(),
+            read_timeout: self.read_timeout.clone(),
+            peer_identity: self.peer_identity.clone(),
+        })
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        if self.tls.is_none() {
+            self.tcp.set_read_timeout(timeout)?;
+        }
+        *self.read_timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp.peer_addr()
+    }
+
+    pub fn peer_identity(&self) -> Option<&str> {
+        self.peer_identity.as_deref()
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+}
+
+impl Read for NetStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(tls) = &self.tls else { return self.tcp.read(buf) };
+        let deadline = self.read_timeout.lock().unwrap().map(|t| Instant::now() + t);
+        loop {
+            let result = tls.lock().unwrap().read(buf);
+            match result {
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    if deadline.is_some_and(|d| Instant::now() >= d) {
+                        return Err(e);
+                    }
+                    thread::sleep(TLS_YIELD);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Write for NetStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &self.tls {
+            Some(tls) => tls.lock().unwrap().write(buf),
+            None => self.tcp.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &self.tls {
+            Some(tls) => tls.lock().unwrap().flush(),
+            None => self.tcp.flush(),
+        }
+    }
+}
+
+// Client-side TLS settings
+pub struct TlsClient {
+    config: Arc<ClientConfig>,
+    // Name to verify in the server certificate; defaults to the host part of the address
+    server_name: Option<String>,
+}
+
+impl TlsClient {
+    // `ca` is the PEM bundle that signed the server certificate.
+    // `identity` is an optional (certificate, key) PEM pair for mutual TLS.
+    pub fn new(ca: &str, identity: Option<(&str, &str)>, server_name: Option<String>) -> Result<Self, String> {
+        let roots = load_roots(ca)?;
+        let builder = ClientConfig::builder().with_root_certificates(roots);
+        let config = match identity {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+                .map_err(|e| format!("Invalid client certificate: {}", e))?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(Self { config: Arc::new(config), server_name })
+    }
+}
+
+// Server-side TLS settings. With `client_ca`, every client must present a certificate signed by it.
+pub fn server_config(cert: &str, key: &str, client_ca: Option<&str>) -> Result<Arc<ServerConfig>, String> {
+    let builder = match client_ca {
+        Some(ca) => {
+            let verifier = WebPkiClientVerifier::builder(Arc::new(load_roots(ca)?))
+                .build()
+                .map_err(|e| format!("Invalid client CA {}: {}", ca, e))?;
+            ServerConfig::builder().with_client_cert_verifier(verifier)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+    let config = builder
+        .with_single_cert(load_certs(cert)?, load_key(key)?)
+        .map_err(|e| format!("Invalid server certificate: {}", e))?;
+    Ok(Arc::new(config))
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Cannot read certificates from {}: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", path));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>, String> {
+    PrivateKeyDer::from_pem_file(path).map_err(|e| format!("Cannot read private key from {}: {}", path, e))
+}
+
+fn load_roots(path: &str) -> Result<RootCertStore, String> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(|e| format!("Invalid CA certificate in {}: {}", path, e))?;
+    }
+    Ok(roots)
+}
+
+// Robot ID carried in a client certificate: the subject Common Name
+fn certificate_identity(cert: &CertificateDer) -> Option<String> {
+    let (_, parsed) = x509_parser::parse_x509_certificate(cert.as_ref()).ok()?;
+    let cn = parsed.subject().iter_common_name().next()?;
+    cn.as_str().ok().map(String::from)
+}